@@ -1,400 +1,4836 @@
 use std::collections::HashMap;
-use std::{env, process};
-use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::env;
+use std::fs::{self, File};
+use std::io::IsTerminal;
 use std::io::prelude::*;
-use std::path::Path;
-use std::time::{SystemTime, UNIX_EPOCH};
-use curl::easy::Easy;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use curl::easy::{Easy, List};
 use serde_json::Value;
 
 const INET_DL_ADDR: &str = "https://cdn.wahrungsrechner.info/api/latest.json";
 const DEFAULT_FILENAME: &str = "currency.json";
+const NAMES_FILENAME: &str = "names.json";
+const CONFIG_FILENAME: &str = "config.toml";
+
+// the currencies shown by --list-usual, independent of which currencies
+// have a name
+const USUAL_CURRENCIES: [&str; 31] = [
+    "AUD", "BGN", "BRL", "CAD", "CHF", "CNY", "CZK", "DKK", "EUR", "GBP", "HKD",
+    "HUF", "IDR", "ILS", "INR", "ISK", "JPY", "KRW", "MXN", "MYR", "NOK", "NZD",
+    "PHP", "PLN", "RON", "SEK", "SGD", "THB", "TRY", "USD", "ZAR",
+];
+// ISO 4217 numeric codes, "840 978 100" is read as "USD EUR 100"
+const NUMERIC_CODES: [(&str, &str); 136] = [
+    ("008", "ALL"), ("012", "DZD"), ("032", "ARS"), ("036", "AUD"), ("044", "BSD"), ("048", "BHD"),
+    ("050", "BDT"), ("051", "AMD"), ("052", "BBD"), ("060", "BMD"), ("064", "BTN"), ("068", "BOB"),
+    ("072", "BWP"), ("084", "BZD"), ("096", "BND"), ("104", "MMK"), ("108", "BIF"), ("116", "KHR"),
+    ("124", "CAD"), ("136", "KYD"), ("144", "LKR"), ("152", "CLP"), ("156", "CNY"), ("170", "COP"),
+    ("188", "CRC"), ("192", "CUP"), ("203", "CZK"), ("208", "DKK"), ("214", "DOP"), ("230", "ETB"),
+    ("242", "FJD"), ("262", "DJF"), ("270", "GMD"), ("292", "GIP"), ("320", "GTQ"), ("324", "GNF"),
+    ("328", "GYD"), ("332", "HTG"), ("340", "HNL"), ("344", "HKD"), ("348", "HUF"), ("352", "ISK"),
+    ("356", "INR"), ("360", "IDR"), ("364", "IRR"), ("368", "IQD"), ("376", "ILS"), ("388", "JMD"),
+    ("392", "JPY"), ("398", "KZT"), ("400", "JOD"), ("404", "KES"), ("410", "KRW"), ("414", "KWD"),
+    ("417", "KGS"), ("418", "LAK"), ("422", "LBP"), ("434", "LYD"), ("446", "MOP"), ("454", "MWK"),
+    ("458", "MYR"), ("462", "MVR"), ("480", "MUR"), ("484", "MXN"), ("496", "MNT"), ("498", "MDL"),
+    ("504", "MAD"), ("512", "OMR"), ("516", "NAD"), ("524", "NPR"), ("554", "NZD"), ("558", "NIO"),
+    ("566", "NGN"), ("578", "NOK"), ("586", "PKR"), ("590", "PAB"), ("598", "PGK"), ("600", "PYG"),
+    ("604", "PEN"), ("608", "PHP"), ("634", "QAR"), ("643", "RUB"), ("646", "RWF"), ("682", "SAR"),
+    ("690", "SCR"), ("702", "SGD"), ("704", "VND"), ("710", "ZAR"), ("748", "SZL"), ("752", "SEK"),
+    ("756", "CHF"), ("760", "SYP"), ("764", "THB"), ("780", "TTD"), ("784", "AED"), ("788", "TND"),
+    ("800", "UGX"), ("807", "MKD"), ("818", "EGP"), ("826", "GBP"), ("834", "TZS"), ("840", "USD"),
+    ("858", "UYU"), ("860", "UZS"), ("882", "WST"), ("886", "YER"), ("901", "TWD"), ("928", "VES"),
+    ("929", "MRU"), ("933", "BYN"), ("934", "TMT"), ("936", "GHS"), ("938", "SDG"), ("941", "RSD"),
+    ("943", "MZN"), ("944", "AZN"), ("946", "RON"), ("949", "TRY"), ("950", "XAF"), ("951", "XCD"),
+    ("952", "XOF"), ("953", "XPF"), ("967", "ZMW"), ("968", "SRD"), ("969", "MGA"), ("971", "AFN"),
+    ("972", "TJS"), ("973", "AOA"), ("975", "BGN"), ("976", "CDF"), ("977", "BAM"), ("978", "EUR"),
+    ("980", "UAH"), ("981", "GEL"), ("985", "PLN"), ("986", "BRL"),
+];
+const DEFAULT_PROVIDER: Provider = Provider { name: "wahrungsrechner", url: INET_DL_ADDR };
+const PROVIDERS: [Provider; 2] = [
+    DEFAULT_PROVIDER,
+    Provider { name: "frankfurter", url: "https://api.frankfurter.app/latest" },
+];
+const TEMP_FILE_SUFFIX: &str = ".part";
+const DIFF_FILE_SUFFIX: &str = ".fresh";
+const HEADERS_FILE_SUFFIX: &str = ".headers";
+const BINARY_CACHE_SUFFIX: &str = ".bin";
+const BINARY_CACHE_MAGIC: &[u8] = b"CCRB2\n";
+const VERIFY_DL_ADDR: &str = "https://api.frankfurter.app/latest";
+const VERIFY_TOLERANCE_BP: f64 = 10.0;
+const VERIFY_PRECISION: usize = 6;
+const ROUND_TRIP_TOLERANCE: f64 = 1e-9;
+// the typical markup of banks and exchange offices on the mid rate, in percent
+const DEFAULT_SPREAD_BAND: (f64, f64) = (1.0, 2.0);
+const DEFAULT_PRECISION: usize = 4;
+const CHANGE_PRECISION: usize = 2;
+const PERCENT_PRECISION: usize = 1;
+const MAX_PRECISION: usize = 12;
+const MAX_WIDENED_PRECISION: usize = 30;
+const DEFAULT_MAX_AGE: u64 = 3_600;
+// how old a cache may be to stand in for a failed download
+const DEFAULT_STALE_OK: u64 = 7 * 86_400;
+const CONNECT_TIMEOUT: u64 = 10;
+const DEFAULT_TIMEOUT: u64 = 30;
+const DEFAULT_RETRIES: u32 = 3;
+const DEFAULT_MIN_CURRENCIES: usize = 20;
+const HEALTH_MIN_CURRENCIES: usize = 30;
+const HEALTH_REQUIRED_CURRENCIES: [&str; 2] = ["EUR", "USD"];
+const DEFAULT_TERMINAL_WIDTH: usize = 80;
+const GRID_GAP: &str = "   ";
+
+// set once by -v/--verbose, read by the verbose! macro everywhere
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+// the file of -o/--output, results go to stdout without it
+static OUTPUT: Mutex<Option<File>> = Mutex::new(None);
+static OUTPUT_FAILED: AtomicBool = AtomicBool::new(false);
+
+/// Like `println!`, but into the -o/--output file when there is one.
+macro_rules! out {
+    () => {
+        write_output(format_args!(""))
+    };
+    ($($arg:tt)*) => {
+        write_output(format_args!($($arg)*))
+    };
+}
+
+/// Like `eprintln!`, but only with -v/--verbose.
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if VERBOSE.load(Ordering::Relaxed) {
+            eprintln!("[verbose] {}", format_args!($($arg)*));
+        }
+    };
+}
+
+/// The optional first word, a spelled-out version of the flags.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Subcommand {
+    Convert,
+    List,
+    Diff,
+    Check,
+}
+
+impl Subcommand {
+    fn parse(word: &str) -> Option<Subcommand> {
+        match word {
+            "convert" => Some(Subcommand::Convert),
+            "list" => Some(Subcommand::List),
+            "diff" => Some(Subcommand::Diff),
+            "check" => Some(Subcommand::Check),
+            _ => None,
+        }
+    }
+}
 
 #[derive(Debug)]
 enum ArgumentResult {
     Success,
     SuccessAndUsualList,
     SuccessAndCompleteList,
+    SuccessAndAuditNames,
+    SuccessAndHealth,
+    SuccessAndDiff,
+    SuccessAndRate,
+    SuccessAndInteractive,
+    SuccessAndBatch,
+    ShowHelp,
+    ShowVersion,
+    ShowCompletions(String),
+    MissingArguments,
     ArgumentError,
 }
 
+/// Error of [`convert`] when a currency isn't in the rates table.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConvertError {
+    UnknownSource(String),
+    UnknownTarget(String),
+}
+
+impl std::fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConvertError::UnknownSource(code) => write!(f, "unknown source currency {}", code),
+            ConvertError::UnknownTarget(code) => write!(f, "unknown target currency {}", code),
+        }
+    }
+}
+
+impl std::error::Error for ConvertError {}
+
+/// Error of [`load_rates`] when the cached rates can't be used.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoadError {
+    Missing(PathBuf),
+    Invalid(PathBuf),
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LoadError::Missing(path) => write!(f, "{} doesn't exist", path.display()),
+            LoadError::Invalid(path) => write!(f, "{} doesn't contain usable rates", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Why [`run`] failed. The discriminant is the exit code of the
+/// program, so every code is defined here and nowhere else.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(i32)]
+pub enum AppError {
+    DownloadFailed = 1,
+    LoadFailed = 2,
+    ArgumentError = 3,
+    UnknownSource = 4,
+    UnknownTarget = 5,
+    MissingNames = 6,
+    DownloadTimedOut = 7,
+    TooFewCurrencies = 8,
+    MissingMajorCurrency = 9,
+    OutputFailed = 10,
+}
+
+impl From<ConvertError> for AppError {
+    fn from(err: ConvertError) -> AppError {
+        match err {
+            ConvertError::UnknownSource(_) => AppError::UnknownSource,
+            ConvertError::UnknownTarget(_) => AppError::UnknownTarget,
+        }
+    }
+}
+
+impl AppError {
+    /// Every variant in the order of its exit code, for the help.
+    pub const ALL: [AppError; 10] = [
+        AppError::DownloadFailed,
+        AppError::LoadFailed,
+        AppError::ArgumentError,
+        AppError::UnknownSource,
+        AppError::UnknownTarget,
+        AppError::MissingNames,
+        AppError::DownloadTimedOut,
+        AppError::TooFewCurrencies,
+        AppError::MissingMajorCurrency,
+        AppError::OutputFailed,
+    ];
+
+    pub fn exit_code(&self) -> i32 {
+        *self as i32
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            AppError::DownloadFailed => "downloading the currency data failed",
+            AppError::LoadFailed => "loading the currency data from disk failed",
+            AppError::ArgumentError => "invalid or missing arguments",
+            AppError::UnknownSource => "the source or base currency is unknown",
+            AppError::UnknownTarget => "the target currency is unknown",
+            AppError::MissingNames => "--audit-names found currencies without a name",
+            AppError::DownloadTimedOut => "downloading the currency data timed out",
+            AppError::TooFewCurrencies => "--health found fewer currencies than expected",
+            AppError::MissingMajorCurrency => "--health found EUR or USD missing",
+            AppError::OutputFailed => "the --output file couldn't be opened or written",
+        }
+    }
+}
+
+#[derive(Debug)]
+enum DownloadResult {
+    Success,
+    TimedOut,
+    Failed,
+    // a 4xx answer or invalid data, another attempt gets the same
+    Rejected,
+    // the data arrived, but couldn't be cached, e.g. on a full disk
+    WriteFailed,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Provider {
+    name: &'static str,
+    url: &'static str,
+}
+
+/// How amounts are rounded to the shown precision. The default rounds
+/// to nearest with halves away from zero, 0.125 -> 0.13.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum RoundMode {
+    #[default]
+    HalfUp,
+    HalfEven,
+    Floor,
+    Ceil,
+}
+
+impl RoundMode {
+    fn parse(mode: &str) -> Option<RoundMode> {
+        match mode {
+            "nearest" | "half-up" => Some(RoundMode::HalfUp),
+            "bankers" | "half-even" => Some(RoundMode::HalfEven),
+            "floor" => Some(RoundMode::Floor),
+            "ceil" => Some(RoundMode::Ceil),
+            _ => None,
+        }
+    }
+}
+
+/// Where a currency is used, for the sections of `--list-usual --grouped`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Region {
+    Europe,
+    Americas,
+    AsiaPacific,
+    AfricaMiddleEast,
+    Other,
+}
+
+impl Region {
+    /// Every region in the order of the sections.
+    pub const ALL: [Region; 5] = [
+        Region::Europe,
+        Region::Americas,
+        Region::AsiaPacific,
+        Region::AfricaMiddleEast,
+        Region::Other,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Region::Europe => "Europe",
+            Region::Americas => "Americas",
+            Region::AsiaPacific => "Asia-Pacific",
+            Region::AfricaMiddleEast => "Africa/Middle East",
+            Region::Other => "Other",
+        }
+    }
+}
+
+/// Information about the rates table besides the rates themselves.
+#[derive(Clone, Debug, Default)]
+struct RatesMeta {
+    as_of: Option<String>,
+    base: Option<String>,
+    source: Option<String>,
+}
+
 #[derive(Clone, Debug)]
 struct ExchangeProcess {
     from: String,
     to: String,
+    targets: Vec<String>,
     rate: f64,
     amount_from: f64,
     amount_to: f64,
+    precision: Option<usize>,
+    rate_precision: Option<usize>,
+    fee: Option<f64>,
+    amount_net: f64,
+    round: RoundMode,
+}
+
+impl ExchangeProcess {
+    fn new() -> ExchangeProcess {
+        ExchangeProcess {
+            from: String::new(),
+            to: String::new(),
+            targets: Vec::new(),
+            rate: 0.0,
+            amount_from: 0.0,
+            amount_to: 0.0,
+            precision: None,
+            rate_precision: None,
+            fee: None,
+            amount_net: 0.0,
+            round: RoundMode::default(),
+        }
+    }
+
+    /// The decimal places of a rate, --rate-precision or else --precision.
+    fn rate_decimals(&self) -> Option<usize> {
+        self.rate_precision.or(self.precision)
+    }
+
+    /// An amount rounded to `precision` places with the chosen mode.
+    fn rounded(&self, amount: f64, precision: usize) -> f64 {
+        Decimal::from_f64(amount)
+            .and_then(|decimal| decimal.round_with(precision as u32, self.round))
+            .map(Decimal::to_f64)
+            .unwrap_or(amount)
+    }
+
+    /// Converts the amount with `rate`, `amount_net` is what is left
+    /// of the converted amount after the fee. The products are exact
+    /// decimals, f64 is only the fallback when they overflow.
+    fn apply_rate(&mut self, rate: f64) {
+        self.rate = rate;
+        self.amount_to = Decimal::product(self.amount_from, rate)
+            .unwrap_or(self.amount_from * rate);
+        let kept = 100.0 - self.fee.unwrap_or(0.0);
+        self.amount_net = Decimal::product(self.amount_to, kept)
+            .map(|net| net / 100.0)
+            .unwrap_or(self.amount_to * kept / 100.0);
+    }
+}
+
+/// A minimal fixed-point decimal, `mantissa / 10^scale`. Amounts and
+/// rates are converted from the shortest decimal form of the f64, so
+/// 1.005 stays 1.005 and rounds to 1.01 instead of 1.00.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Decimal {
+    mantissa: i128,
+    scale: u32,
+}
+
+impl Decimal {
+    fn from_f64(value: f64) -> Option<Decimal> {
+        if !value.is_finite() {
+            return None;
+        }
+        Decimal::parse(&value.to_string())
+    }
+
+    fn parse(text: &str) -> Option<Decimal> {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text),
+        };
+        let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+
+        let mut mantissa: i128 = 0;
+        for c in integer.chars().chain(fraction.chars()) {
+            let digit = c.to_digit(10)? as i128;
+            mantissa = mantissa.checked_mul(10)?.checked_add(digit)?;
+        }
+        if negative {
+            mantissa = -mantissa;
+        }
+
+        Some(Decimal { mantissa, scale: fraction.len() as u32 })
+    }
+
+    fn mul(self, other: Decimal) -> Option<Decimal> {
+        Some(Decimal {
+            mantissa: self.mantissa.checked_mul(other.mantissa)?,
+            scale: self.scale + other.scale,
+        })
+    }
+
+    /// Rounds half away from zero to `precision` decimal places.
+    fn round(self, precision: u32) -> Option<Decimal> {
+        self.round_with(precision, RoundMode::HalfUp)
+    }
+
+    fn round_with(self, precision: u32, mode: RoundMode) -> Option<Decimal> {
+        if self.scale <= precision {
+            let factor = 10i128.checked_pow(precision - self.scale)?;
+            return Some(Decimal { mantissa: self.mantissa.checked_mul(factor)?, scale: precision });
+        }
+
+        // the division truncates towards zero, the remainder decides
+        // whether to step away from it
+        let divisor = 10i128.checked_pow(self.scale - precision)?;
+        let mut mantissa = self.mantissa / divisor;
+        let remainder = self.mantissa % divisor;
+        let away = match mode {
+            RoundMode::HalfUp => remainder.abs() * 2 >= divisor,
+            RoundMode::HalfEven => {
+                let twice = remainder.abs() * 2;
+                twice > divisor || (twice == divisor && mantissa % 2 != 0)
+            }
+            RoundMode::Floor => remainder < 0,
+            RoundMode::Ceil => remainder > 0,
+        };
+        if away {
+            mantissa += self.mantissa.signum();
+        }
+        Some(Decimal { mantissa, scale: precision })
+    }
+
+    fn to_f64(self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+
+    /// The exact product of two f64 amounts, rounded back to f64.
+    fn product(a: f64, b: f64) -> Option<f64> {
+        Some(Decimal::from_f64(a)?.mul(Decimal::from_f64(b)?)?.to_f64())
+    }
+}
+
+impl std::fmt::Display for Decimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, integer, fraction)
+    }
+}
+
+/// Formats `value` with `precision` decimal places, rounding its
+/// decimal form half away from zero.
+fn format_amount(value: f64, precision: usize) -> String {
+    match Decimal::from_f64(value).and_then(|decimal| decimal.round(precision as u32)) {
+        Some(decimal) => decimal.to_string(),
+        None => format!("{:.*}", precision, value),
+    }
+}
+
+/// The digit grouping and the decimal mark of a locale, only ASCII
+/// characters so the tables stay aligned.
+#[derive(Debug, Clone, Copy)]
+struct NumberLocale {
+    group: char,
+    decimal: char,
+}
+
+impl NumberLocale {
+    /// Accepts tags like de-DE, de_DE.UTF-8 or fr; None for an unknown
+    /// language.
+    fn parse(tag: &str) -> Option<NumberLocale> {
+        let tag = tag.split('.').next().unwrap_or_default().replace('_', "-").to_ascii_lowercase();
+        let language = tag.split('-').next().unwrap_or_default();
+        let (group, decimal) = match (language, tag.as_str()) {
+            (_, "de-ch" | "it-ch" | "fr-ch" | "rm-ch") => ('\'', '.'),
+            ("en" | "ja" | "zh" | "ko" | "he" | "th" | "ms" | "ga", _) => (',', '.'),
+            ("de" | "nl" | "it" | "es" | "pt" | "da" | "id" | "tr" | "el" | "ro" | "hr" | "sl", _) => ('.', ','),
+            ("fr" | "ru" | "pl" | "cs" | "sk" | "sv" | "nb" | "no" | "fi" | "uk" | "hu" | "bg" | "et" | "lt" | "lv", _) => (' ', ','),
+            _ => return None,
+        };
+        Some(NumberLocale { group, decimal })
+    }
+
+    /// Regroups a plain number like -1234567.8900 into 1.234.567,8900.
+    fn apply(&self, number: &str) -> String {
+        let (sign, digits) = match number.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None => ("", number),
+        };
+        let (integer, fraction) = match digits.split_once('.') {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (digits, None),
+        };
+
+        let mut grouped = String::from(sign);
+        for (index, digit) in integer.chars().enumerate() {
+            if index > 0 && (integer.len() - index) % 3 == 0 {
+                grouped.push(self.group);
+            }
+            grouped.push(digit);
+        }
+        if let Some(fraction) = fraction {
+            grouped.push(self.decimal);
+            grouped.push_str(fraction);
+        }
+        grouped
+    }
+}
+
+/// An amount for humans, grouped when a locale was requested.
+fn format_number(value: f64, precision: usize, options: &Options) -> String {
+    let number = format_amount(value, precision);
+    match &options.locale {
+        Some(locale) => locale.apply(&number),
+        None => number,
+    }
+}
+
+/// Removes a partially written file when it goes out of scope,
+/// unless it was moved into place with `persist()`.
+struct TempFileGuard {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl TempFileGuard {
+    fn new(path: PathBuf) -> TempFileGuard {
+        TempFileGuard {
+            path,
+            keep: false,
+        }
+    }
+
+    fn persist(mut self, target: &Path) -> std::io::Result<()> {
+        fs::rename(&self.path, target)?;
+        self.keep = true;
+        Ok(())
+    }
+}
+
+impl Drop for TempFileGuard {
+    fn drop(&mut self) {
+        if !self.keep {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// The color set of --theme, only used while color is on.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum Theme {
+    #[default]
+    Default,
+    Light,
+    Mono,
+    HighContrast,
+}
+
+impl Theme {
+    fn parse(theme: &str) -> Option<Theme> {
+        match theme {
+            "default" => Some(Theme::Default),
+            "light" => Some(Theme::Light),
+            "mono" => Some(Theme::Mono),
+            "high-contrast" => Some(Theme::HighContrast),
+            _ => None,
+        }
+    }
+}
+
+/// The ANSI escape codes of the output, all empty when color is off.
+#[derive(Clone, Copy, Debug)]
+struct Palette {
+    bold: &'static str,
+    label: &'static str,
+    reset: &'static str,
+    code: &'static str,
+    amount: &'static str,
+    debit: &'static str,
+    default: &'static str,
+}
+
+impl Palette {
+    fn new(color: bool, theme: Theme) -> Palette {
+        if !color {
+            return Palette {
+                bold: "",
+                label: "",
+                reset: "",
+                code: "",
+                amount: "",
+                debit: "",
+                default: "",
+            };
+        }
+        match theme {
+            Theme::Default => Palette {
+                bold: "\x1B[1m",
+                label: "\x1B[24m",
+                reset: "\x1B[0m",
+                code: "\x1B[92m",
+                amount: "\x1B[93m",
+                debit: "\x1B[91m",
+                default: "\x1B[39m",
+            },
+            // the bright colors are hard to read on a white background
+            Theme::Light => Palette {
+                bold: "\x1B[1m",
+                label: "\x1B[24m",
+                reset: "\x1B[0m",
+                code: "\x1B[32m",
+                amount: "\x1B[34m",
+                debit: "\x1B[31m",
+                default: "\x1B[39m",
+            },
+            // no colors at all, debits are underlined instead of red
+            Theme::Mono => Palette {
+                bold: "\x1B[1m",
+                label: "\x1B[24m",
+                reset: "\x1B[0m",
+                code: "\x1B[1m",
+                amount: "\x1B[1m",
+                debit: "\x1B[1;4m",
+                default: "\x1B[22;24m",
+            },
+            // bold, and magenta rather than red for colorblind users
+            Theme::HighContrast => Palette {
+                bold: "\x1B[1m",
+                label: "\x1B[24m",
+                reset: "\x1B[0m",
+                code: "\x1B[1;96m",
+                amount: "\x1B[1;93m",
+                debit: "\x1B[1;95m",
+                default: "\x1B[22;39m",
+            },
+        }
+    }
+
+    fn amount_color(&self, amount: f64) -> &'static str {
+        // debits are shown in red to set them apart
+        if amount < 0.0 {
+            self.debit
+        } else {
+            self.amount
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Options {
+    providers: Vec<Provider>,
+    verify: bool,
+    check: bool,
+    show_path: bool,
+    json: bool,
+    color: bool,
+    theme: Theme,
+    max_age: u64,
+    stale_ok: u64,
+    min_currencies: usize,
+    force: bool,
+    offline: bool,
+    url: Option<String>,
+    timeout: u64,
+    retries: u32,
+    reverse: bool,
+    base: Option<String>,
+    rates_file: Option<PathBuf>,
+    output: Option<PathBuf>,
+    compare: Option<PathBuf>,
+    table_base: Option<String>,
+    proxy: Option<String>,
+    api_key: Option<String>,
+    search: Option<String>,
+    usual: Option<Vec<String>>,
+    grouped: bool,
+    default_from: Option<String>,
+    default_to: Option<String>,
+    all_targets: bool,
+    rank: bool,
+    round_trip: bool,
+    spread: Option<(f64, f64)>,
+    percent_of: Option<(f64, String)>,
+    copy: bool,
+    // the amount comes from stdin, read once the arguments are parsed
+    stdin_amount: bool,
+    symbol: bool,
+    locale: Option<NumberLocale>,
+    quiet: bool,
+}
+
+impl Options {
+    fn new() -> Options {
+        Options {
+            providers: vec![DEFAULT_PROVIDER],
+            verify: false,
+            check: false,
+            show_path: false,
+            json: false,
+            color: true,
+            theme: Theme::Default,
+            max_age: DEFAULT_MAX_AGE,
+            stale_ok: DEFAULT_STALE_OK,
+            min_currencies: DEFAULT_MIN_CURRENCIES,
+            force: false,
+            offline: false,
+            url: None,
+            timeout: DEFAULT_TIMEOUT,
+            retries: DEFAULT_RETRIES,
+            reverse: false,
+            base: None,
+            rates_file: None,
+            output: None,
+            compare: None,
+            table_base: None,
+            proxy: None,
+            api_key: None,
+            search: None,
+            usual: None,
+            grouped: false,
+            default_from: None,
+            default_to: None,
+            all_targets: false,
+            rank: false,
+            round_trip: false,
+            spread: None,
+            percent_of: None,
+            copy: false,
+            stdin_amount: false,
+            symbol: false,
+            locale: None,
+            quiet: false,
+        }
+    }
+}
+
+/// Converts `amount` from one currency into another with the given
+/// rates table, e.g. the one returned by [`load_rates`].
+pub fn convert(rates: &HashMap<String, f64>, from: &str, to: &str, amount: f64) -> Result<f64, ConvertError> {
+    Ok(convert_with_rates(rates, from, to, amount)?.amount_to)
+}
+
+/// The whole conversion of `amount` without any IO, so it can be
+/// checked against a fixed rates table.
+fn convert_with_rates(rates: &HashMap<String, f64>, from: &str, to: &str, amount: f64) -> Result<ExchangeProcess, ConvertError> {
+    let mut exchange = ExchangeProcess::new();
+    exchange.amount_from = amount;
+    convert_pair(&exchange, rates, from, to)
+}
+
+/// A copy of `exchange`, precision and fee included, converted from
+/// `from` to `to`.
+fn convert_pair(exchange: &ExchangeProcess, rates: &HashMap<String, f64>, from: &str, to: &str) -> Result<ExchangeProcess, ConvertError> {
+    let rate = exchange_rate(rates, from, to)?;
+    let mut pair = exchange.clone();
+    pair.from = from.to_string();
+    pair.to = to.to_string();
+    pair.apply_rate(rate);
+    Ok(pair)
+}
+
+/// Loads the locally cached rates of the default provider, without
+/// downloading anything.
+pub fn load_rates() -> Result<HashMap<String, f64>, LoadError> {
+
+    let file_name = get_rates_file_path(&DEFAULT_PROVIDER);
+    if !file_name.exists() {
+        return Err(LoadError::Missing(file_name));
+    }
+
+    let mut rates = HashMap::new();
+    if !load_rates_file_from_disk(&file_name, &mut rates, &mut RatesMeta::default()) {
+        return Err(LoadError::Invalid(file_name));
+    }
+
+    Ok(rates)
+
+}
+
+/// Re-expresses the rates table against `base`, so the rate of `base`
+/// becomes 1.0. Returns `None` if `base` isn't in the table.
+pub fn rebase_rates(rates: &HashMap<String, f64>, base: &str) -> Option<HashMap<String, f64>> {
+    let base_rate = *rates.get(base).filter(|rate| **rate != 0.0)?;
+    Some(rates.iter()
+        .map(|(code, rate)| (code.to_string(), rate / base_rate))
+        .collect())
+}
+
+fn exchange_rate(rates: &HashMap<String, f64>, from: &str, to: &str) -> Result<f64, ConvertError> {
+    let rate_from = rates.get(from).ok_or_else(|| ConvertError::UnknownSource(from.to_string()))?;
+    let rate_to = rates.get(to).ok_or_else(|| ConvertError::UnknownTarget(to.to_string()))?;
+    Ok(rate_to / rate_from)
+}
+
+pub fn run() -> Result<(), AppError> {
+    let result = run_to_output();
+    if OUTPUT_FAILED.load(Ordering::Relaxed) {
+        return result.and(Err(AppError::OutputFailed));
+    }
+    result
+}
+
+/// Appends a line to the -o/--output file, or prints it.
+fn write_output(line: std::fmt::Arguments) {
+    let mut output = OUTPUT.lock().unwrap_or_else(|err| err.into_inner());
+    match output.as_mut() {
+        Some(file) => {
+            if let Err(err) = writeln!(file, "{}", line) {
+                // report the first failure only, not every line
+                if !OUTPUT_FAILED.swap(true, Ordering::Relaxed) {
+                    eprintln!("Couldn't write to the output file (error: {}).", err);
+                }
+            }
+        }
+        None => println!("{}", line),
+    }
 }
 
-impl ExchangeProcess {
-    fn new() -> ExchangeProcess {
-        ExchangeProcess {
-            from: String::new(),
-            to: String::new(),
-            rate: 0.0,
-            amount_from: 0.0,
-            amount_to: 0.0,
+fn run_to_output() -> Result<(), AppError> {
+
+    let mut rates: HashMap<String, f64> = HashMap::new();
+    let mut meta = RatesMeta::default();
+    let mut exchange = ExchangeProcess::new();
+    let mut options = Options::new();
+
+    let args: Vec<String> = env::args().collect();
+    let prg_name = args.first().cloned().unwrap_or_default();
+
+    // parse_arguments never exits, all exit decisions are made here
+    let func = parse_arguments(&args, &mut exchange, &mut options);
+    match func {
+        ArgumentResult::ArgumentError => return Err(AppError::ArgumentError),
+        ArgumentResult::MissingArguments => return Err(AppError::ArgumentError),
+        ArgumentResult::ShowHelp => {
+            print_help(&prg_name);
+            return Ok(());
+        }
+        ArgumentResult::ShowVersion => {
+            println!("{} v{}\n", prg_name, env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        ArgumentResult::ShowCompletions(shell) => {
+            if !print_completions(&shell, &prg_name, &options) {
+                return Err(AppError::ArgumentError);
+            }
+            return Ok(());
+        }
+        _ => (),
+    }
+
+    if options.stdin_amount {
+        match read_amount_from_stdin() {
+            Some(amount) => exchange.amount_from = amount,
+            None => {
+                eprintln!("Couldn't read an amount from stdin.");
+                return Err(AppError::ArgumentError);
+            }
+        }
+    }
+
+    // repeated runs append, so the file becomes a log of conversions
+    if let Some(path) = &options.output {
+        match File::options().create(true).append(true).open(path) {
+            Ok(file) => *OUTPUT.lock().unwrap_or_else(|err| err.into_inner()) = Some(file),
+            Err(err) => {
+                eprintln!("Couldn't open the output file {} (error: {}).", path.display(), err);
+                return Err(AppError::OutputFailed);
+            }
+        }
+    }
+
+    // see https://no-color.org, and don't write escape codes into files or pipes
+    let no_color_env = env::var_os("NO_COLOR").is_some_and(|val| !val.is_empty());
+    if options.json || options.quiet || no_color_env || options.output.is_some() || !std::io::stdout().is_terminal() {
+        options.color = false;
+    }
+    let palette = Palette::new(options.color, options.theme);
+
+    // the audit always needs the latest data from the provider
+    let force_download = options.force || matches!(func, ArgumentResult::SuccessAndAuditNames);
+    // and a check of the provider must not pass on a cached copy
+    let strict = matches!(func, ArgumentResult::SuccessAndHealth | ArgumentResult::SuccessAndAuditNames);
+    // a read-only cache is fine as long as nothing is downloaded
+    if !options.offline && options.rates_file.is_none() && !check_cache_dir_override() {
+        return Err(AppError::LoadFailed);
+    }
+    verbose!("cache directory: {}", get_temp_dir());
+
+    if let ArgumentResult::SuccessAndDiff = func {
+        return run_diff(&options);
+    }
+
+    // an explicit file bypasses the providers, the download and the
+    // freshness check
+    let rates_path = match &options.rates_file {
+        Some(path) => {
+            if !path.is_file() {
+                eprintln!("The rates file {} doesn't exist.", path.display());
+                return Err(AppError::LoadFailed);
+            }
+            if !load_rates_file(path, &mut rates, &mut meta) {
+                eprintln!("Error loading currency data from {}.", path.display());
+                return Err(AppError::LoadFailed);
+            }
+            path.clone()
+        }
+        None => {
+            let (provider, file_name) = match load_from_providers(&options, force_download, strict, &mut rates, &mut meta) {
+                Ok(loaded) => loaded,
+                Err(err) => {
+                    if let ArgumentResult::SuccessAndHealth = func {
+                        print_health_failure(&err);
+                    }
+                    return Err(err);
+                }
+            };
+            if options.providers.len() > 1 {
+                eprintln!("Using the currency data of {}.", provider.name);
+            }
+            file_name
+        }
+    };
+
+    // without a date in the data, the age of the file has to do
+    let as_of = meta.as_of
+        .or_else(|| get_modified_time(&rates_path).map(format_timestamp))
+        .unwrap_or_else(|| String::from("an unknown date"));
+
+    if let Some(source) = &meta.source {
+        verbose!("the rates come from {}", source);
+    }
+
+    // the base the rates are relative to, after a --base
+    options.table_base = options.base.clone().or_else(|| meta.base.clone());
+
+    if let Some(base) = &options.base {
+        if meta.base.as_ref() == Some(base) {
+            verbose!("the rates already have {} as their base", base);
+        }
+        rates = match rebase_rates(&rates, base) {
+            Some(rebased) => rebased,
+            None => {
+                eprintln!("The base currency {} is not in the rates table.", base);
+                return Err(AppError::UnknownSource);
+            }
+        };
+    }
+
+    match func {
+        ArgumentResult::SuccessAndUsualList => {
+            let names = load_currency_names();
+            print_usual_rates(&rates, &names, &as_of, exchange.rate_decimals(), &options, &palette);
+            return Ok(());
+        }
+        ArgumentResult::SuccessAndCompleteList => {
+            let names = load_currency_names();
+            print_all_rates(&rates, &names, &as_of, exchange.rate_decimals(), &options, &palette);
+            return Ok(());
+        }
+        ArgumentResult::SuccessAndAuditNames => {
+            if !audit_currency_names(&rates) {
+                return Err(AppError::MissingNames);
+            }
+            return Ok(());
+        }
+        ArgumentResult::SuccessAndHealth => {
+            return check_health(&rates, &as_of);
+        }
+        _ => (),
+    }
+
+    if let ArgumentResult::SuccessAndInteractive = func {
+        run_interactive(&exchange, &rates, &as_of, &options);
+        return Ok(());
+    }
+
+    if let ArgumentResult::SuccessAndBatch = func {
+        run_batch(&exchange, &rates, &as_of, &options);
+        return Ok(());
+    }
+
+    if let ArgumentResult::SuccessAndRate = func {
+        return print_rates_only(&exchange, &rates, &as_of, &options);
+    }
+
+    if options.all_targets {
+        let mut targets: Vec<_> = rates.keys()
+            .filter(|key| **key != exchange.from)
+            .cloned()
+            .collect();
+        targets.sort();
+        exchange.targets = targets;
+    }
+
+    if let Some(path) = &options.compare {
+        let mut old_rates: HashMap<String, f64> = HashMap::new();
+        let mut old_meta = RatesMeta::default();
+        if !path.is_file() || !load_rates_file(path, &mut old_rates, &mut old_meta) {
+            eprintln!("Error loading the older rates from {}.", path.display());
+            return Err(AppError::LoadFailed);
+        }
+        if let Some(base) = &options.base {
+            old_rates = rebase_rates(&old_rates, base).unwrap_or_default();
+        }
+        let since = old_meta.as_of
+            .or_else(|| get_modified_time(path).map(format_timestamp))
+            .unwrap_or_else(|| String::from("an unknown date"));
+        return print_rate_changes(&exchange, &old_rates, &rates, &since, &options);
+    }
+
+    if options.rank {
+        return print_ranking(&exchange, &rates, &as_of, &options);
+    }
+
+    if options.round_trip {
+        return print_round_trips(&exchange, &rates, &as_of, &options);
+    }
+
+    if let Some(band) = options.spread {
+        return print_spreads(&exchange, &rates, &as_of, band, &options);
+    }
+
+    if let Some((reference, _)) = options.percent_of {
+        return print_percent_of(&exchange, &rates, &as_of, reference, &options);
+    }
+
+    print_conversions(&exchange, &rates, &as_of, &options)
+
+}
+
+/// Compares the cached rates with a fresh download, which is kept
+/// apart so the cache itself stays untouched.
+fn run_diff(options: &Options) -> Result<(), AppError> {
+
+    let provider = options.providers.first().copied().unwrap_or(DEFAULT_PROVIDER);
+    let file_name = get_rates_file_path(&provider);
+
+    let mut old_rates: HashMap<String, f64> = HashMap::new();
+    if !file_name.exists() || !load_rates_file_from_disk(&file_name, &mut old_rates, &mut RatesMeta::default()) {
+        eprintln!("There is no cached copy of {} to compare with.", provider.name);
+        return Err(AppError::LoadFailed);
+    }
+
+    let mut fresh_name = file_name.into_os_string();
+    fresh_name.push(DIFF_FILE_SUFFIX);
+    let fresh = TempFileGuard::new(PathBuf::from(fresh_name));
+    let _fresh_headers = TempFileGuard::new(get_headers_path(&fresh.path));
+
+    let url = options.url.as_deref().unwrap_or(provider.url);
+    let request = FetchRequest::new(url, options);
+    // the fresh copy never has validators, so it is always downloaded
+    match download_rates_file(&fresh.path, &request, options.retries, true) {
+        DownloadResult::Success => (),
+        DownloadResult::TimedOut => {
+            eprintln!("Timeout while downloading the currency data from {}.", provider.name);
+            return Err(AppError::DownloadTimedOut);
+        }
+        DownloadResult::Failed | DownloadResult::Rejected | DownloadResult::WriteFailed => {
+            eprintln!("Error downloading the currency data from {}.", provider.name);
+            return Err(AppError::DownloadFailed);
+        }
+    }
+
+    let mut new_rates: HashMap<String, f64> = HashMap::new();
+    if !load_rates_file(&fresh.path, &mut new_rates, &mut RatesMeta::default()) {
+        return Err(AppError::LoadFailed);
+    }
+
+    if let Some(base) = &options.base {
+        match (rebase_rates(&old_rates, base), rebase_rates(&new_rates, base)) {
+            (Some(old), Some(new)) => {
+                old_rates = old;
+                new_rates = new;
+            }
+            _ => {
+                eprintln!("The base currency {} is not in both rates tables.", base);
+                return Err(AppError::UnknownSource);
+            }
+        }
+    }
+
+    print_rates_diff(&diff_rates(&old_rates, &new_rates), options);
+
+    Ok(())
+
+}
+
+/// The currencies whose rate differs, as (code, old, new): the changed
+/// ones by the size of the change, then the added and the removed.
+fn diff_rates(old_rates: &HashMap<String, f64>, new_rates: &HashMap<String, f64>) -> Vec<(String, Option<f64>, Option<f64>)> {
+
+    let percent = |old: f64, new: f64| if old == 0.0 { f64::INFINITY } else { ((new - old) / old * 100.0).abs() };
+
+    let mut changed: Vec<(String, Option<f64>, Option<f64>)> = Vec::new();
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    for (code, new) in new_rates.iter() {
+        match old_rates.get(code) {
+            Some(old) if old != new => changed.push((code.to_string(), Some(*old), Some(*new))),
+            Some(_) => (),
+            None => added.push((code.to_string(), None, Some(*new))),
+        }
+    }
+    for (code, old) in old_rates.iter() {
+        if !new_rates.contains_key(code) {
+            removed.push((code.to_string(), Some(*old), None));
+        }
+    }
+
+    changed.sort_by(|a, b| {
+        let (a_old, a_new) = (a.1.unwrap_or(0.0), a.2.unwrap_or(0.0));
+        let (b_old, b_new) = (b.1.unwrap_or(0.0), b.2.unwrap_or(0.0));
+        percent(b_old, b_new).total_cmp(&percent(a_old, a_new)).then_with(|| a.0.cmp(&b.0))
+    });
+    added.sort_by(|a, b| a.0.cmp(&b.0));
+    removed.sort_by(|a, b| a.0.cmp(&b.0));
+
+    changed.extend(added);
+    changed.extend(removed);
+    changed
+
+}
+
+fn print_rates_diff(diff: &[(String, Option<f64>, Option<f64>)], options: &Options) {
+
+    if options.json {
+        let diff: Vec<Value> = diff.iter()
+            .map(|(code, old, new)| {
+                let change = old.zip(*new).map(|(old, new)| new - old);
+                let percent = old.zip(*new)
+                    .filter(|(old, _)| *old != 0.0)
+                    .map(|(old, new)| (new - old) / old * 100.0);
+                serde_json::json!({
+                    "code": code,
+                    "old_rate": old,
+                    "new_rate": new,
+                    "change": change,
+                    "change_percent": percent,
+                })
+            })
+            .collect();
+        out!("{}", Value::Array(diff));
+        return;
+    }
+
+    if diff.is_empty() {
+        out!("No rate changed since the cached copy.");
+        return;
+    }
+
+    let precision = |rate: f64| rate_precision(rate, None);
+    for (code, old, new) in diff.iter() {
+        match (old, new) {
+            (Some(old), Some(new)) => {
+                let change = new - old;
+                let percent = if *old == 0.0 { String::from("new") } else { format!("{:+.2}%", change / old * 100.0) };
+                // enough places that a small change doesn't vanish
+                let places = precision(*old).max(precision(change));
+                out!("{:<4} {} -> {}  {}{}  {}",
+                     code,
+                     format_number(*old, places, options),
+                     format_number(*new, places, options),
+                     if change > 0.0 { "+" } else { "" },
+                     format_number(change, places, options),
+                     percent);
+            }
+            (None, Some(new)) => out!("{:<4} added with {}", code, format_number(*new, precision(*new), options)),
+            (Some(old), None) => out!("{:<4} removed, was {}", code, format_number(*old, precision(*old), options)),
+            (None, None) => (),
+        }
+    }
+
+}
+
+/// The one-line status of --health, the exit code tells the failure
+/// class apart for monitoring.
+fn check_health(rates: &HashMap<String, f64>, as_of: &str) -> Result<(), AppError> {
+
+    if let Some(missing) = HEALTH_REQUIRED_CURRENCIES.iter().find(|code| !rates.contains_key(**code)) {
+        out!("FAIL: {} is missing from the currency data of {}.", missing, as_of);
+        return Err(AppError::MissingMajorCurrency);
+    }
+
+    if rates.len() < HEALTH_MIN_CURRENCIES {
+        out!("FAIL: only {} currencies, expected at least {}.", rates.len(), HEALTH_MIN_CURRENCIES);
+        return Err(AppError::TooFewCurrencies);
+    }
+
+    out!("OK: {} currencies as of {}.", rates.len(), as_of);
+    Ok(())
+
+}
+
+fn print_health_failure(err: &AppError) {
+    match err {
+        AppError::DownloadFailed => out!("FAIL: the currency data couldn't be downloaded."),
+        AppError::DownloadTimedOut => out!("FAIL: downloading the currency data timed out."),
+        AppError::LoadFailed => out!("FAIL: the currency data couldn't be loaded."),
+        _ => out!("FAIL: exit code {}.", err.exit_code()),
+    }
+}
+
+/// Loads the rates of the first provider in the chain that delivers
+/// usable data, downloading them when the local copy is too old.
+/// The provider whose data ended up in `rates`, with its cache file.
+fn load_from_providers(options: &Options, force_download: bool, strict: bool, rates: &mut HashMap<String, f64>, meta: &mut RatesMeta) -> Result<(Provider, PathBuf), AppError> {
+
+    // try the providers in order until one of them delivers usable data
+    let mut last_error = AppError::DownloadFailed;
+    let mut used_provider = None;
+    for provider in options.providers.iter() {
+
+        // the cache path is computed once and handed to every step
+        let file_name = get_rates_file_path(provider);
+        let mut downloaded = false;
+        if options.offline {
+            match get_modified_time(&file_name) {
+                Some(time) => eprintln!("Offline, using cached rates from {}.", format_timestamp(time)),
+                None => {
+                    eprintln!("Offline, but there is no local copy of {}.", file_name.display());
+                    last_error = AppError::LoadFailed;
+                    continue;
+                }
+            }
+        } else if force_download || !check_rates_file(&file_name, options.max_age) {
+            if let Err(error) = refresh_rates_file(provider, &file_name, options, force_download, strict) {
+                last_error = error;
+                continue;
+            }
+            downloaded = true;
+        }
+
+        rates.clear();
+        *meta = RatesMeta::default();
+        if !load_rates_file_from_disk(&file_name, rates, meta) {
+            eprintln!("Error loading currency data of {} from disk.", provider.name);
+            last_error = AppError::LoadFailed;
+            continue;
+        }
+
+        // a table that parses but has only a handful of currencies is
+        // a degraded answer, so it is downloaded once more
+        if rates.len() < options.min_currencies {
+            eprintln!("The currency data of {} has only {} currencies, expected at least {}.", provider.name, rates.len(), options.min_currencies);
+            if options.offline || downloaded {
+                last_error = AppError::LoadFailed;
+                continue;
+            }
+            // without the validators the server can't answer 304
+            let _ = fs::remove_file(get_headers_path(&file_name));
+            if let Err(error) = refresh_rates_file(provider, &file_name, options, true, strict) {
+                last_error = error;
+                continue;
+            }
+            rates.clear();
+            *meta = RatesMeta::default();
+            if !load_rates_file_from_disk(&file_name, rates, meta) || rates.len() < options.min_currencies {
+                eprintln!("The new currency data of {} is still incomplete ({} currencies).", provider.name, rates.len());
+                last_error = AppError::LoadFailed;
+                continue;
+            }
+        }
+
+        used_provider = Some((*provider, file_name));
+        break;
+
+    }
+
+    used_provider.ok_or(last_error)
+
+}
+
+/// Downloads the rates of `provider` into its cache file. Ok means
+/// there is a local copy to load, maybe an older one.
+/// With `strict` a failed download is never replaced by the local copy.
+fn refresh_rates_file(provider: &Provider, file_name: &Path, options: &Options, force: bool, strict: bool) -> Result<(), AppError> {
+
+    let url = options.url.as_deref().unwrap_or(provider.url);
+    verbose!("downloading the rates of {}{}", provider.name, if force { " (forced)" } else { "" });
+    let request = FetchRequest::new(url, options);
+    let result = download_rates_file(file_name, &request, options.retries, force);
+    let failure = match result {
+        DownloadResult::Success => None,
+        DownloadResult::TimedOut => {
+            eprintln!("Timeout while downloading the currency data from {}.", provider.name);
+            Some(AppError::DownloadTimedOut)
+        }
+        DownloadResult::Failed | DownloadResult::Rejected => {
+            eprintln!("Error downloading the currency data from {}.", provider.name);
+            Some(AppError::DownloadFailed)
+        }
+        DownloadResult::WriteFailed => {
+            eprintln!("Couldn't cache the currency data from {}.", provider.name);
+            Some(AppError::DownloadFailed)
+        }
+    };
+    if let Some(error) = failure {
+        // --health and --audit-names judge the provider and a forced
+        // download asks for fresh rates, an old copy would hide that
+        // the download failed
+        if strict || force {
+            return Err(error);
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|t| t.as_secs()).unwrap_or(0);
+        let age = match get_modified_time(file_name) {
+            Some(time) => now.saturating_sub(time),
+            None => return Err(error),
+        };
+        if age > options.stale_ok {
+            eprintln!("The local copy of {} is {} s old, more than --stale-ok-seconds {} allows.", provider.name, age, options.stale_ok);
+            return Err(error);
+        }
+
+        // a stale copy beats no rates at all during an outage, or when
+        // the new rates couldn't be cached
+        if matches!(result, DownloadResult::WriteFailed) {
+            eprintln!("Using the existing local copy of {} instead.", provider.name);
+        } else {
+            eprintln!("Warning: using the stale local copy of {}, it is {} s old.", provider.name, age);
+        }
+    }
+
+    Ok(())
+
+}
+
+/// Reads conversions like "USD EUR 100" from stdin until EOF or "quit",
+/// using the rates loaded once at startup.
+fn run_interactive(exchange: &ExchangeProcess, rates: &HashMap<String, f64>, as_of: &str, options: &Options) {
+
+    let prompt = std::io::stdin().is_terminal();
+    if prompt {
+        println!("Rates as of {}. Enter a conversion like 'USD EUR 100', or 'quit'.", as_of);
+    }
+
+    let mut line = String::new();
+    loop {
+        if prompt {
+            print!("> ");
+            let _ = std::io::stdout().flush();
+        }
+
+        line.clear();
+        match std::io::stdin().read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => (),
+            Err(err) => {
+                eprintln!("Couldn't read from stdin (error: {}).", err);
+                break;
+            }
+        }
+
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line == "quit" || line == "exit" {
+            break;
+        }
+
+        let mut line_exchange = exchange.clone();
+        let positionals = line.split_whitespace().map(String::from).collect();
+        if let Err(err) = parse_conversion(positionals, &mut line_exchange, options) {
+            eprintln!("{}", err);
+            continue;
+        }
+
+        // errors are already reported, keep going with the next line
+        let _ = print_conversions(&line_exchange, rates, as_of, options);
+    }
+
+}
+
+/// Converts every stdin line of the form "FROM TO AMOUNT" and prints
+/// exactly one line for each, so the output lines up with the input.
+/// Malformed lines give an "error:" line instead of stopping the batch.
+fn run_batch(exchange: &ExchangeProcess, rates: &HashMap<String, f64>, as_of: &str, options: &Options) {
+
+    for (index, line) in std::io::stdin().lock().lines().enumerate() {
+
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                eprintln!("Couldn't read from stdin (error: {}).", err);
+                break;
+            }
+        };
+
+        // blank lines and comments give no output
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match batch_conversion(line, exchange, rates, options) {
+            Ok(pair) => {
+                if options.json {
+                    out!("{}", exchange_to_json(&pair, rates, as_of, options));
+                } else if options.quiet {
+                    out!("{}", format_quiet(&pair));
+                } else {
+                    let palette = Palette::new(false, Theme::Default);
+                    let mut result = format!("{} = {}",
+                             format_money(&pair.from, pair.amount_from, display_precision(&pair.from, pair.amount_from, pair.precision), options, &palette),
+                             format_money(&pair.to, pair.amount_to, display_precision(&pair.to, pair.amount_to, pair.precision), options, &palette));
+                    if let Some(fee) = pair.fee {
+                        let precision_net = display_precision(&pair.to, pair.amount_net, pair.precision);
+                        result = format!("{}, {} after a {}% fee", result, format_money(&pair.to, pair.amount_net, precision_net, options, &palette), fee);
+                    }
+                    out!("{}", result);
+                }
+            }
+            Err(err) => {
+                if options.json {
+                    out!("{}", serde_json::json!({ "error": err, "line": index + 1 }));
+                } else {
+                    out!("error: line {}: {}", index + 1, err);
+                }
+            }
+        }
+
+    }
+
+}
+
+fn batch_conversion(line: &str, exchange: &ExchangeProcess, rates: &HashMap<String, f64>, options: &Options) -> Result<ExchangeProcess, String> {
+
+    let mut parsed = exchange.clone();
+    let positionals: Vec<String> = line.split_whitespace().map(String::from).collect();
+    parse_conversion(positionals, &mut parsed, options)?;
+    if parsed.targets.len() != 1 {
+        return Err(format!("expected FROM TO AMOUNT, got: {}", line));
+    }
+
+    let (mut from, mut to) = (parsed.from.clone(), parsed.targets.remove(0));
+    if options.reverse {
+        std::mem::swap(&mut from, &mut to);
+    }
+
+    convert_pair(&parsed, rates, &from, &to).map_err(|err| err.to_string())
+
+}
+
+/// The pair of the amount and `target`, or of `target` and the amount
+/// with --reverse; an unknown target is reported.
+fn convert_target(exchange: &ExchangeProcess, rates: &HashMap<String, f64>, target: &str, options: &Options) -> Option<ExchangeProcess> {
+
+    // reversed, the amount is given in the target currency
+    let (from, to) = if options.reverse {
+        (target, exchange.from.as_str())
+    } else {
+        (exchange.from.as_str(), target)
+    };
+
+    match convert_pair(exchange, rates, from, to) {
+        Ok(pair) => Some(pair),
+        Err(_) => {
+            report_unknown_currency(target, rates, options.json || options.quiet);
+            None
+        }
+    }
+
+}
+
+/// Prints the pair of every target with `row`, which returns its JSON
+/// with --json, then the JSON and `footer`. Unknown targets and failed
+/// rows don't stop the others, the first error is returned at the end.
+fn print_per_target<F>(exchange: &ExchangeProcess, rates: &HashMap<String, f64>, options: &Options, footer: Option<String>, mut row: F) -> Result<(), AppError>
+where
+    F: FnMut(&ExchangeProcess) -> Result<Option<Value>, AppError>,
+{
+
+    if !rates.contains_key(&exchange.from) {
+        report_unknown_currency(&exchange.from, rates, options.json || options.quiet);
+        return Err(AppError::UnknownSource);
+    }
+
+    let mut error = None;
+    let mut results: Vec<Value> = Vec::new();
+    for target in exchange.targets.iter() {
+        let result = match convert_target(exchange, rates, target, options) {
+            Some(pair) => row(&pair),
+            None => Err(AppError::UnknownTarget),
+        };
+        match result {
+            Ok(Some(result)) => results.push(result),
+            Ok(None) => (),
+            Err(err) => {
+                error.get_or_insert(err);
+            }
+        }
+    }
+
+    if options.json {
+        // a single target is printed as an object, several as an array
+        if exchange.targets.len() == 1 {
+            if let Some(result) = results.pop() {
+                out!("{}", result);
+            }
+        } else {
+            out!("{}", Value::Array(results));
+        }
+    } else if let Some(footer) = footer.filter(|_| !options.quiet) {
+        out!("{}", footer);
+    }
+
+    match error {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+
+}
+
+fn print_conversions(exchange: &ExchangeProcess, rates: &HashMap<String, f64>, as_of: &str, options: &Options) -> Result<(), AppError> {
+
+    let mut copied: Vec<String> = Vec::new();
+    let footer = format!("Rates as of {}{}.", as_of, base_suffix(options));
+    let result = print_per_target(exchange, rates, options, Some(footer), |pair| {
+
+        if options.copy {
+            copied.push(format_quiet(pair));
+        }
+
+        if options.json {
+            return Ok(Some(exchange_to_json(pair, rates, as_of, options)));
+        } else if options.quiet {
+            out!("{}", format_quiet(pair));
+        } else {
+            print_exchange(pair, rates, options);
+        }
+        Ok(None)
+
+    });
+
+    // a missing clipboard never fails the conversion
+    if !copied.is_empty() && !copy_to_clipboard(&copied.join("\n")) {
+        eprintln!("Warning: no clipboard is available, nothing was copied.");
+    }
+
+    result
+
+}
+
+/// Only the rate of each pair, "1 EUR = 1.0850 USD", without an
+/// amount to convert.
+fn print_rates_only(exchange: &ExchangeProcess, rates: &HashMap<String, f64>, as_of: &str, options: &Options) -> Result<(), AppError> {
+
+    let footer = format!("Rates as of {}{}.", as_of, base_suffix(options));
+    print_per_target(exchange, rates, options, Some(footer), |pair| {
+
+        let rate = format_number(pair.rate, rate_precision(pair.rate, pair.rate_decimals()), options);
+        if options.json {
+            let mut json = serde_json::Map::new();
+            json.insert(String::from("from"), Value::from(pair.from.as_str()));
+            json.insert(String::from("to"), Value::from(pair.to.as_str()));
+            json.insert(String::from("rate"), Value::from(pair.rate));
+            return Ok(Some(Value::Object(json)));
+        } else if options.quiet {
+            out!("{}", rate);
+        } else {
+            out!("1 {} = {} {}", pair.from, rate, pair.to);
+        }
+        Ok(None)
+
+    })
+
+}
+
+/// The change of the from->to cross rate between two snapshots, in
+/// percent of the old rate.
+fn rate_change(old_rates: &HashMap<String, f64>, new_rates: &HashMap<String, f64>, from: &str, to: &str) -> Result<(f64, f64, f64), String> {
+
+    let missing = |err| match err {
+        ConvertError::UnknownSource(code) | ConvertError::UnknownTarget(code) => code,
+    };
+    let old_rate = exchange_rate(old_rates, from, to)
+        .map_err(|err| format!("{} is missing in the older snapshot.", missing(err)))?;
+    let new_rate = exchange_rate(new_rates, from, to)
+        .map_err(|err| format!("{} is missing in the current rates.", missing(err)))?;
+
+    if old_rate == 0.0 {
+        return Err(format!("The older rate of {}->{} is zero.", from, to));
+    }
+
+    Ok((old_rate, new_rate, (new_rate / old_rate - 1.0) * 100.0))
+
+}
+
+fn print_rate_changes(exchange: &ExchangeProcess, old_rates: &HashMap<String, f64>, new_rates: &HashMap<String, f64>, since: &str, options: &Options) -> Result<(), AppError> {
+
+    // a currency missing in the older snapshot is reported, but
+    // doesn't stop the others
+    let precision = exchange.precision.unwrap_or(CHANGE_PRECISION);
+    print_per_target(exchange, new_rates, options, None, |pair| {
+
+        let (from, to) = (&pair.from, &pair.to);
+        let (old_rate, new_rate, change) = rate_change(old_rates, new_rates, from, to).map_err(|message| {
+            eprintln!("{}", message);
+            AppError::UnknownTarget
+        })?;
+
+        if options.json {
+            return Ok(Some(serde_json::json!({
+                "from": from,
+                "to": to,
+                "old_rate": old_rate,
+                "new_rate": new_rate,
+                "change_percent": change,
+                "since": since,
+            })));
+        } else if options.quiet {
+            out!("{:+.*}", precision, change);
+        } else {
+            out!("{}->{} moved {:+.*}% since {}.", from, to, precision, change, since);
+        }
+        Ok(None)
+
+    })
+
+}
+
+/// Hands `text` to the first clipboard tool that works: wl-copy and
+/// xclip or xsel on Linux, pbcopy on macOS, clip on Windows.
+fn copy_to_clipboard(text: &str) -> bool {
+
+    let tools: &[(&str, &[&str])] = if cfg!(target_os = "windows") {
+        &[("clip", &[])]
+    } else if cfg!(target_os = "macos") {
+        &[("pbcopy", &[])]
+    } else {
+        &[("wl-copy", &[]), ("xclip", &["-selection", "clipboard"]), ("xsel", &["--clipboard", "--input"])]
+    };
+
+    for (tool, args) in tools.iter() {
+        let mut child = match Command::new(tool).args(*args).stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::null()).spawn() {
+            Ok(child) => child,
+            Err(_) => continue,
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(text.as_bytes());
+        }
+        if child.wait().is_ok_and(|status| status.success()) {
+            verbose!("copied {} to the clipboard with {}", text, tool);
+            return true;
+        }
+    }
+
+    false
+
+}
+
+/// The targets ranked by how much of them the amount buys, highest
+/// first, with the rate per unit of the source.
+fn print_ranking(exchange: &ExchangeProcess, rates: &HashMap<String, f64>, as_of: &str, options: &Options) -> Result<(), AppError> {
+
+    if !rates.contains_key(&exchange.from) {
+        report_unknown_currency(&exchange.from, rates, options.json || options.quiet);
+        return Err(AppError::UnknownSource);
+    }
+
+    let mut pairs: Vec<ExchangeProcess> = exchange.targets.iter()
+        .filter_map(|target| convert_target(exchange, rates, target, options))
+        .collect();
+    let unknown_target = pairs.len() < exchange.targets.len();
+    pairs.sort_by(|a, b| b.amount_to.total_cmp(&a.amount_to));
+
+    if options.json {
+        let ranking: Vec<Value> = pairs.iter()
+            .enumerate()
+            .map(|(index, pair)| {
+                let mut json = exchange_to_json(pair, rates, as_of, options);
+                json["rank"] = Value::from(index + 1);
+                json
+            })
+            .collect();
+        out!("{}", Value::Array(ranking));
+    } else if options.quiet {
+        for pair in pairs.iter() {
+            out!("{} {}", pair.to, format_quiet(pair));
+        }
+    } else {
+        let rows: Vec<_> = pairs.iter()
+            .map(|pair| {
+                let precision = display_precision(&pair.to, pair.amount_to, pair.precision);
+                let amount = format_number(pair.rounded(pair.amount_to, precision), precision, options);
+                let rate = format_number(pair.rate, rate_precision(pair.rate, pair.rate_decimals()), options);
+                (pair.to.as_str(), amount, rate)
+            })
+            .collect();
+        let amount_width = rows.iter().map(|row| row.1.len()).max().unwrap_or(0).max("Amount".len());
+        let rate_width = rows.iter().map(|row| row.2.len()).max().unwrap_or(0).max("Rate".len());
+
+        out!("{} {} buys:\n", exchange.from, format_number(exchange.amount_from, display_precision(&exchange.from, exchange.amount_from, exchange.precision), options));
+        out!("   # | Abbr | {:>amount_width$} | {:>rate_width$}", "Amount", "Rate");
+        out!("-----|------|-{}-|-{}", "-".repeat(amount_width), "-".repeat(rate_width));
+        for (index, (code, amount, rate)) in rows.iter().enumerate() {
+            out!(" {:>3} | {:<4} | {:>amount_width$} | {:>rate_width$}", index + 1, code, amount, rate);
+        }
+        out!("\nRates as of {}{}.", as_of, base_suffix(options));
+    }
+
+    if unknown_target {
+        return Err(AppError::UnknownTarget);
+    }
+
+    Ok(())
+
+}
+
+/// What is left of the amount after converting it into each target
+/// and back, paying the fee both ways.
+fn print_round_trips(exchange: &ExchangeProcess, rates: &HashMap<String, f64>, as_of: &str, options: &Options) -> Result<(), AppError> {
+
+    let fee = exchange.fee.unwrap_or(0.0);
+    let precision = display_precision(&exchange.from, exchange.amount_from, exchange.precision);
+    let footer = format!("Rates as of {}{}.", as_of, base_suffix(options));
+    print_per_target(exchange, rates, options, Some(footer), |there| {
+
+        let target = &there.to;
+        let mut back = there.clone();
+        back.amount_from = there.amount_net;
+        let back = convert_pair(&back, rates, target, &exchange.from)?;
+        let loss = back.amount_net - exchange.amount_from;
+
+        if options.json {
+            return Ok(Some(serde_json::json!({
+                "from": exchange.from,
+                "via": target,
+                "amount_from": exchange.amount_from,
+                "fee_percent": fee,
+                "amount_there": there.amount_net,
+                "amount_back": back.amount_net,
+                "loss": loss,
+                "rates_as_of": as_of,
+            })));
+        } else if options.quiet {
+            out!("{}", format_amount(back.rounded(back.amount_net, precision), precision));
+        } else {
+            out!("Converting {} {} -> {} -> {} at a {}% fee leaves you {} {} ({}).",
+                     format_number(exchange.amount_from, precision, options),
+                     exchange.from,
+                     target,
+                     exchange.from,
+                     fee,
+                     format_number(back.rounded(back.amount_net, precision), precision, options),
+                     exchange.from,
+                     format_number(back.rounded(loss, precision), precision, options)
+                     );
+        }
+        Ok(None)
+
+    })
+
+}
+
+/// The mid-market amount and the range a bank would likely charge
+/// around it, `band` percent above when buying and below when selling.
+fn print_spreads(exchange: &ExchangeProcess, rates: &HashMap<String, f64>, as_of: &str, band: (f64, f64), options: &Options) -> Result<(), AppError> {
+
+    let (low, high) = band;
+    let footer = format!("A {}-{}% spread around the mid-market rate, rates as of {}{}.", low, high, as_of, base_suffix(options));
+    print_per_target(exchange, rates, options, Some(footer), |pair| {
+
+        let mid = pair.amount_to;
+        let buy = (mid * (1.0 + low / 100.0), mid * (1.0 + high / 100.0));
+        let sell = (mid * (1.0 - high / 100.0), mid * (1.0 - low / 100.0));
+
+        if options.json {
+            return Ok(Some(serde_json::json!({
+                "from": pair.from,
+                "to": pair.to,
+                "amount_from": pair.amount_from,
+                "mid": mid,
+                "buy": [buy.0, buy.1],
+                "sell": [sell.0, sell.1],
+                "band_percent": [low, high],
+                "rates_as_of": as_of,
+            })));
+        }
+
+        // amounts of the target, so -p and the currency's decimals apply
+        let number = |value: f64| {
+            let precision = display_precision(&pair.to, value, pair.precision);
+            format_number(pair.rounded(value, precision), precision, options)
+        };
+        if options.quiet {
+            out!("{} {} {} {} {}", number(mid), number(buy.0), number(buy.1), number(sell.0), number(sell.1));
+        } else {
+            out!("Mid {} {} = {} {}; you'd likely pay {}-{} {} at a bank, or get {}-{} {} selling.",
+                     format_number(pair.amount_from, display_precision(&pair.from, pair.amount_from, pair.precision), options),
+                     pair.from,
+                     number(mid),
+                     pair.to,
+                     number(buy.0),
+                     number(buy.1),
+                     pair.to,
+                     number(sell.0),
+                     number(sell.1),
+                     pair.to
+                     );
+        }
+        Ok(None)
+
+    })
+
+}
+
+/// The converted amount as a percentage of the --percent-of amount,
+/// which is already in the target currency.
+fn print_percent_of(exchange: &ExchangeProcess, rates: &HashMap<String, f64>, as_of: &str, reference: f64, options: &Options) -> Result<(), AppError> {
+
+    if !rates.contains_key(&exchange.from) {
+        report_unknown_currency(&exchange.from, rates, options.json || options.quiet);
+        return Err(AppError::UnknownSource);
+    }
+
+    let pair = match exchange.targets.first().map(|target| convert_pair(exchange, rates, &exchange.from, target)) {
+        Some(Ok(pair)) => pair,
+        _ => {
+            report_unknown_currency(exchange.targets.first().map(String::as_str).unwrap_or_default(), rates, options.json || options.quiet);
+            return Err(AppError::UnknownTarget);
+        }
+    };
+    let percent = pair.amount_to / reference * 100.0;
+
+    if options.json {
+        out!("{}", serde_json::json!({
+            "from": pair.from,
+            "to": pair.to,
+            "amount_from": pair.amount_from,
+            "amount_to": pair.amount_to,
+            "reference": reference,
+            "percent": percent,
+            "rates_as_of": as_of,
+        }));
+    } else if options.quiet {
+        out!("{}", format_amount(percent, PERCENT_PRECISION));
+    } else {
+        let precision_from = display_precision(&pair.from, pair.amount_from, pair.precision);
+        let precision_to = display_precision(&pair.to, pair.amount_to, pair.precision);
+        let precision_reference = display_precision(&pair.to, reference, pair.precision);
+        out!("{} {} = {} {}, {}% of {} {}.",
+                 pair.from,
+                 format_number(pair.amount_from, precision_from, options),
+                 pair.to,
+                 format_number(pair.rounded(pair.amount_to, precision_to), precision_to, options),
+                 format_number(percent, PERCENT_PRECISION, options),
+                 pair.to,
+                 format_number(reference, precision_reference, options)
+                 );
+        out!("Rates as of {}{}.", as_of, base_suffix(options));
+    }
+
+    Ok(())
+
+}
+
+/// ", base EUR" for the "Rates as of" lines, when the base is known.
+fn base_suffix(options: &Options) -> String {
+    match &options.table_base {
+        Some(base) => format!(", base {}", base),
+        None => String::new(),
+    }
+}
+
+/// Just the converted amount for --quiet, after the fee if there is one.
+fn format_quiet(exchange: &ExchangeProcess) -> String {
+    let precision = display_precision(&exchange.to, exchange.amount_net, exchange.precision);
+    format_amount(exchange.rounded(exchange.amount_net, precision), precision)
+}
+
+fn report_unknown_currency(currency: &str, rates: &HashMap<String, f64>, to_stderr: bool) {
+
+    let mut message = format!("Did not found currency {}.", currency);
+
+    let suggestions = suggest_currencies(currency, rates.keys().map(String::as_str));
+    if !suggestions.is_empty() {
+        message = format!("{} Did you mean: {}?", message, suggestions.join(", "));
+    }
+
+    // keep stdout clean for the JSON document or the plain number
+    if to_stderr {
+        eprintln!("{}", message);
+    } else {
+        out!("{}", message);
+    }
+
+}
+
+/// Up to three of `codes` that are close to the mistyped one, the
+/// closest first.
+fn suggest_currencies<'a>(currency: &str, codes: impl Iterator<Item = &'a str>) -> Vec<String> {
+
+    // with three letter codes a bigger distance matches almost anything
+    let max_distance = if currency.len() > 4 { 2 } else { 1 };
+
+    let mut candidates: Vec<_> = codes
+        .map(|key| (levenshtein(currency, key), key))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    candidates.sort();
+
+    candidates.into_iter().take(3).map(|(_, key)| key.to_string()).collect()
+
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+
+    row[b.len()]
+
+}
+
+fn display_precision(currency: &str, amount: f64, precision: Option<usize>) -> usize {
+    // an explicit precision always wins, otherwise an amount gets the
+    // decimals of its currency, 14857 JPY but 100.00 USD
+    match precision {
+        Some(precision) => precision,
+        None => widen_precision(amount, minor_units(currency) as usize),
+    }
+}
+
+/// Like display_precision, but rates keep DEFAULT_PRECISION places
+/// in every currency, a JPY rate of 161.2000 isn't an amount of yen.
+/// Callers pass ExchangeProcess::rate_decimals, so --rate-precision
+/// can differ from the precision of the amounts.
+fn rate_precision(rate: f64, precision: Option<usize>) -> usize {
+    match precision {
+        Some(precision) => precision,
+        None => widen_precision(rate, DEFAULT_PRECISION),
+    }
+}
+
+/// More decimal places for tiny amounts like 0.0000152 BTC, which would
+/// otherwise be printed as 0.0000; two significant digits are kept.
+fn widen_precision(amount: f64, precision: usize) -> usize {
+    let amount = amount.abs();
+    if amount == 0.0 || !amount.is_finite() || amount >= 0.5 * 10f64.powi(-(precision as i32)) {
+        return precision;
+    }
+    let leading_zeros = (-amount.log10()).ceil() as usize;
+    (leading_zeros + 1).clamp(precision, MAX_WIDENED_PRECISION)
+}
+
+/// "USD 100.00", or "$100.00 (USD)" with --symbol; the code stays
+/// because several currencies share a symbol.
+fn format_money(currency: &str, amount: f64, precision: usize, options: &Options, palette: &Palette) -> String {
+    match get_currency_symbol(currency) {
+        Some(symbol) if options.symbol => {
+            let sign = if amount < 0.0 { "-" } else { "" };
+            format!("{}{}{}{}{} {}({}){}",
+                    palette.amount_color(amount), sign, symbol, format_number(amount.abs(), precision, options), palette.default,
+                    palette.code, currency, palette.default)
+        }
+        _ => format!("{}{}{} {}{}{}",
+                     palette.code, currency, palette.default,
+                     palette.amount_color(amount), format_number(amount, precision, options), palette.default),
+    }
+}
+
+fn print_exchange(exchange: &ExchangeProcess, rates: &HashMap<String, f64>, options: &Options) {
+
+    let precision_from = display_precision(&exchange.from, exchange.amount_from, exchange.precision);
+    let precision_to = display_precision(&exchange.to, exchange.amount_to, exchange.precision);
+    let amount_to = exchange.rounded(exchange.amount_to, precision_to);
+
+    let palette = Palette::new(options.color, options.theme);
+
+    out!("{}Actual exchange rate:{} {} = {}",
+             palette.label,
+             palette.reset,
+             format_money(&exchange.from, exchange.amount_from, precision_from, options, &palette),
+             format_money(&exchange.to, amount_to, precision_to, options, &palette)
+             );
+
+    // the amounts alone don't show the rate, so it is added when its
+    // precision was asked for
+    if exchange.rate_precision.is_some() {
+        out!("{}Rate:{}                 1 {} = {} {}",
+                 palette.label,
+                 palette.reset,
+                 exchange.from,
+                 format_number(exchange.rate, rate_precision(exchange.rate, exchange.rate_decimals()), options),
+                 exchange.to
+                 );
+    }
+
+    // the cross rate is the step into the base and the step out of it
+    if options.show_path {
+        let amount_base = exchange.amount_from / rates[&exchange.from];
+        let amount_via = amount_base * rates[&exchange.to];
+        let base = options.table_base.as_deref().unwrap_or("base");
+        let precision_base = display_precision(base, amount_base, exchange.precision);
+        let precision_via = display_precision(&exchange.to, amount_via, exchange.precision);
+        out!("{}Via the base:{}         {} -> {} -> {}",
+                 palette.label,
+                 palette.reset,
+                 format_money(&exchange.from, exchange.amount_from, precision_from, options, &palette),
+                 format_money(base, exchange.rounded(amount_base, precision_base), precision_base, options, &palette),
+                 format_money(&exchange.to, exchange.rounded(amount_via, precision_via), precision_via, options, &palette)
+                 );
+    }
+
+    if let Some(fee) = exchange.fee {
+        let precision_net = display_precision(&exchange.to, exchange.amount_net, exchange.precision);
+        out!("{}After a {}% fee:{} {}",
+                 palette.label,
+                 fee,
+                 palette.reset,
+                 format_money(&exchange.to, exchange.rounded(exchange.amount_net, precision_net), precision_net, options, &palette)
+                 );
+    }
+
+    if options.check {
+        let amount_back = exchange.amount_to * (rates[&exchange.from] / rates[&exchange.to]);
+        out!("{}Converted back:{}       {} = {}",
+                 palette.label,
+                 palette.reset,
+                 format_money(&exchange.to, amount_to, precision_to, options, &palette),
+                 format_money(&exchange.from, amount_back, display_precision(&exchange.from, amount_back, exchange.precision), options, &palette)
+                 );
+    }
+
+    if options.verify {
+        // the check needs more places than a shown rate by default
+        let verify_precision = exchange.rate_precision.unwrap_or(VERIFY_PRECISION);
+        let (inverse, product) = round_trip(rates, exchange);
+        out!("Inverse rate {}/{}: {:.*}, forward * inverse: {:.12}",
+                 exchange.to,
+                 exchange.from,
+                 verify_precision,
+                 inverse,
+                 product
+                 );
+        if !round_trip_ok(product) {
+            out!("Warning: the rates don't round-trip, the currency data may be corrupt.");
+        }
+
+        if let Some(direct) = fetch_direct_rate(&exchange.from, &exchange.to, options) {
+            let diff_bp = rate_difference_bp(exchange.rate, direct);
+            out!("Direct rate {}/{}: {:.*}, computed: {:.*}, difference: {:+.2} bp",
+                     exchange.from,
+                     exchange.to,
+                     verify_precision,
+                     direct,
+                     verify_precision,
+                     exchange.rate,
+                     diff_bp
+                     );
+            if diff_bp.abs() > VERIFY_TOLERANCE_BP {
+                out!("Warning: the difference exceeds the tolerance of {} bp.", VERIFY_TOLERANCE_BP);
+            }
+        }
+    }
+
+}
+
+fn exchange_to_json(exchange: &ExchangeProcess, rates: &HashMap<String, f64>, as_of: &str, options: &Options) -> Value {
+
+    let mut json = serde_json::json!({
+        "from": exchange.from,
+        "to": exchange.to,
+        "rate": exchange.rate,
+        "amount_from": exchange.amount_from,
+        "amount_to": exchange.amount_to,
+        "rates_as_of": as_of,
+    });
+
+    if let Some(base) = &options.table_base {
+        json["base"] = Value::from(base.as_str());
+    }
+
+    if let Some(fee) = exchange.fee {
+        json["fee_percent"] = Value::from(fee);
+        json["amount_net"] = Value::from(exchange.amount_net);
+    }
+
+    if options.check {
+        json["amount_back"] = Value::from(exchange.amount_to / exchange.rate);
+    }
+
+    if options.show_path {
+        json["amount_base"] = Value::from(exchange.amount_from / rates[&exchange.from]);
+    }
+
+    if options.verify {
+        let (inverse, product) = round_trip(rates, exchange);
+        json["inverse_rate"] = Value::from(inverse);
+        json["round_trip_ok"] = Value::from(round_trip_ok(product));
+        if !round_trip_ok(product) {
+            eprintln!("Warning: the rates don't round-trip, the currency data may be corrupt.");
+        }
+
+        if let Some(direct) = fetch_direct_rate(&exchange.from, &exchange.to, options) {
+            json["direct_rate"] = Value::from(direct);
+            json["difference_bp"] = Value::from(rate_difference_bp(exchange.rate, direct));
+        }
+    }
+
+    json
+
+}
+
+fn get_modified_time(file_name: &Path) -> Option<u64> {
+    let time = fs::metadata(file_name).ok()?.modified().ok()?;
+    time.duration_since(UNIX_EPOCH).ok().map(|t| t.as_secs())
+}
+
+/// Formats seconds since the epoch as "YYYY-MM-DD HH:MM:SS UTC".
+fn format_timestamp(secs: u64) -> String {
+
+    let days = (secs / 86_400) as i64;
+    let time = secs % 86_400;
+
+    // civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+            year,
+            month,
+            day,
+            time / 3_600,
+            time % 3_600 / 60,
+            time % 60
+            )
+
+}
+
+fn check_rates_file(file_name: &Path, max_age: u64) -> bool {
+
+    if !file_name.exists() {
+        eprintln!("A local copy of {} didn't exist.", file_name.display());
+        return false;
+    }
+
+    let file = match File::open(file_name) {
+        Ok(file) => file,
+        Err(err) => {
+            eprintln!("Couldn't open {} (error: {}).", file_name.display(), err);
+            return false
+        },
+    };
+
+    let metadata = match file.metadata() {
+        Ok(metadata) => metadata,
+        Err(err) => {
+            eprintln!("Couldn't get metadata from file {} (error: {}).", file_name.display(), err);
+            return false
+        },
+    };
+
+    let mut file_date: u64 = 0;
+    if let Ok(time) = metadata.modified() {
+        if let Ok(t) = time.duration_since(UNIX_EPOCH) {
+            file_date = t.as_secs();
+        }
+    }
+
+    let mut cur_date: u64 = 0;
+    let now = SystemTime::now();
+    if let Ok(t) = now.duration_since(UNIX_EPOCH) {
+        cur_date = t.as_secs();
+    }
+
+    // a file from the future, e.g. after a clock correction, would
+    // otherwise look fresh until the clock catches up
+    if file_date > cur_date {
+        eprintln!("Warning: {} was modified {} s in the future, check the system clock; refreshing it.",
+                  file_name.display(), file_date - cur_date);
+        return false;
+    }
+
+    let age = cur_date.saturating_sub(file_date);
+    verbose!("cache file {} is {} s old (max age {} s)", file_name.display(), age, max_age);
+    if age >= max_age {
+        return false;
+    }
+
+    true
+
+}
+
+/// Downloads `request` into `file_name`. With `force` the cache
+/// validators aren't sent, so the server can't answer 304.
+fn download_rates_file(file_name: &Path, request: &FetchRequest, retries: u32, force: bool) -> DownloadResult {
+
+    // transient failures are retried after 1, 2, 4, ... seconds
+    let mut delay = 1;
+    let mut attempt = 0;
+    loop {
+        let result = try_download_rates_file(file_name, request, force);
+        // only timeouts, lost connections and 5xx answers may go away,
+        // and a full disk won't be any emptier on the next attempt
+        if !matches!(result, DownloadResult::TimedOut | DownloadResult::Failed) || attempt >= retries {
+            return result;
+        }
+        attempt += 1;
+        eprintln!("Retrying the download in {} s ({} of {}).", delay, attempt, retries);
+        std::thread::sleep(Duration::from_secs(delay));
+        delay *= 2;
+    }
+
+}
+
+fn try_download_rates_file(file_name: &Path, request: &FetchRequest, force: bool) -> DownloadResult {
+
+    let url = request.url;
+
+    let mut temp_name = file_name.as_os_str().to_os_string();
+    temp_name.push(TEMP_FILE_SUFFIX);
+    let temp_name = PathBuf::from(temp_name);
+
+    // the body is kept in memory until it is complete, so Ctrl-C during
+    // the transfer leaves nothing behind; only a run killed while
+    // writing the temp file can, and that is cleaned up here
+    if temp_name.exists() {
+        verbose!("removing {} left over from an interrupted run", temp_name.display());
+        let _ = fs::remove_file(&temp_name);
+    }
+
+    // ask the server to skip the body if our copy is still current
+    let cached = if force { CacheValidators::default() } else { load_cache_validators(file_name, url) };
+    let request = FetchRequest { cached, ..*request };
+
+    let (data, mut received) = match fetch_rates(&request) {
+        Ok(Fetched::Body(data, received)) => (data, received),
+        Ok(Fetched::NotModified) if file_name.exists() => {
+            // unchanged upstream, so the old copy counts as fresh again
+            verbose!("{} is not modified", url);
+            if let Err(err) = File::options().write(true).open(file_name).and_then(|file| file.set_modified(SystemTime::now())) {
+                eprintln!("Couldn't touch {} (error: {}).", file_name.display(), err);
+            }
+            return DownloadResult::Success;
+        }
+        Ok(Fetched::NotModified) => {
+            eprintln!("The server answered with HTTP status 304, but there is no local copy.");
+            return DownloadResult::Rejected;
+        }
+        Err(result) => return result,
+    };
+
+    if let Err(reason) = validate_rates_json(&data) {
+        eprintln!("The downloaded data is invalid, keeping the old copy: {}.", reason);
+        return DownloadResult::Rejected;
+    }
+
+    // write a temp file first, so a failed write never replaces a
+    // good local copy
+    let guard = TempFileGuard::new(temp_name);
+    if let Err(err) = fs::write(&guard.path, &data) {
+        eprintln!("Failed to write the cache file {} (error: {}).", guard.path.display(), err);
+        return DownloadResult::WriteFailed;
+    }
+
+    if let Err(err) = guard.persist(file_name) {
+        eprintln!("Couldn't replace {} (error: {}).", file_name.display(), err);
+        return DownloadResult::WriteFailed;
+    }
+    received.url = Some(url.to_string());
+    save_cache_validators(file_name, &received);
+
+    DownloadResult::Success
+
+}
+
+/// Everything a download backend needs for one GET of the rates.
+struct FetchRequest<'a> {
+    url: &'a str,
+    timeout: u64,
+    proxy: Option<&'a str>,
+    api_key: Option<&'a str>,
+    progress: bool,
+    cached: CacheValidators,
+}
+
+impl<'a> FetchRequest<'a> {
+    /// A plain GET of `url` with the timeout, proxy and API key of the
+    /// command line and no validators.
+    fn new(url: &'a str, options: &'a Options) -> Self {
+        FetchRequest {
+            url,
+            timeout: options.timeout,
+            proxy: options.proxy.as_deref(),
+            // the key belongs to the endpoint of --url, the built-in
+            // providers never get it
+            api_key: options.api_key.as_deref().filter(|_| options.url.as_deref() == Some(url)),
+            // progress on stderr only when someone is watching it
+            progress: !options.quiet && std::io::stderr().is_terminal(),
+            cached: CacheValidators::default(),
+        }
+    }
+}
+
+/// A successful GET, either the body with its validators or the
+/// server's word that the cached copy is still current.
+enum Fetched {
+    Body(Vec<u8>, CacheValidators),
+    NotModified,
+}
+
+/// The curl backend. Another HTTP client only has to provide this
+/// function, the checks and the cache handling stay the same.
+fn fetch_rates(request: &FetchRequest) -> Result<Fetched, DownloadResult> {
+
+    let url = request.url;
+
+    verbose!("GET {}", url);
+    let mut handle = Easy::new();
+    if let Err(err) = handle.url(url) {
+        eprintln!("Invalid url {} (error: {}).", url, err);
+        return Err(DownloadResult::Rejected);
+    }
+    set_timeouts(&mut handle, request.timeout);
+    set_compression(&mut handle);
+    let proxy = set_proxy(&mut handle, url, request.proxy);
+
+    let mut request_headers = List::new();
+    if let Some(etag) = &request.cached.etag {
+        let _ = request_headers.append(&format!("If-None-Match: {}", etag));
+    }
+    if let Some(last_modified) = &request.cached.last_modified {
+        let _ = request_headers.append(&format!("If-Modified-Since: {}", last_modified));
+    }
+    // the key itself never shows up in the verbose output
+    if let Some(key) = request.api_key {
+        verbose!("sending an API key");
+        let _ = request_headers.append(&format!("Authorization: Bearer {}", key));
+    }
+    if let Err(err) = handle.http_headers(request_headers) {
+        eprintln!("Couldn't set the request headers (error: {}).", err);
+    }
+    if request.progress {
+        let _ = handle.progress(true);
+    }
+
+    let mut data = Vec::new();
+    let mut received = CacheValidators::default();
+    {
+        let mut transfer = handle.transfer();
+        transfer.write_function(|chunk| {
+            data.extend_from_slice(chunk);
+            Ok(chunk.len())
+        }).unwrap();
+        transfer.header_function(|header| {
+            received.read_header(header);
+            true
+        }).unwrap();
+        if request.progress {
+            transfer.progress_function(|total, done, _, _| {
+                if total > 0.0 {
+                    eprint!("\rDownloading rates... {:3.0}%", done / total * 100.0);
+                } else {
+                    eprint!("\rDownloading rates... {} bytes", done as u64);
+                }
+                true
+            }).unwrap();
+        }
+
+        let result = transfer.perform();
+        if request.progress {
+            eprintln!("\rDownloading rates... done.    ");
+        }
+        if let Err(err) = result {
+            report_transfer_error(&err, proxy.as_deref());
+            if err.is_operation_timedout() {
+                return Err(DownloadResult::TimedOut)
+            }
+            if err.is_unsupported_protocol() || err.is_url_malformed() {
+                return Err(DownloadResult::Rejected)
+            }
+            return Err(DownloadResult::Failed)
+        }
+    }
+
+    // error pages must never end up in the cache
+    let status = handle.response_code();
+    if let Ok(code) = status {
+        verbose!("HTTP status {}", code);
+    }
+    match status {
+        Ok(200) => Ok(Fetched::Body(data, received)),
+        Ok(304) => Ok(Fetched::NotModified),
+        Ok(code @ 400..=499) => {
+            eprintln!("The server answered with HTTP status {}.", code);
+            Err(DownloadResult::Rejected)
+        }
+        Ok(code) => {
+            eprintln!("The server answered with HTTP status {}.", code);
+            Err(DownloadResult::Failed)
+        }
+        Err(err) => {
+            eprintln!("Couldn't get the HTTP status (error: {}).", err);
+            Err(DownloadResult::Failed)
+        }
+    }
+
+}
+
+/// The ETag and Last-Modified headers of the cached download, kept in
+/// a sidecar file next to it for the next conditional request.
+#[derive(Debug, Default)]
+struct CacheValidators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    // the validators only hold for the url they came from
+    url: Option<String>,
+}
+
+impl CacheValidators {
+    fn read_header(&mut self, header: &[u8]) {
+        let header = String::from_utf8_lossy(header);
+        if let Some((name, value)) = header.split_once(':') {
+            let value = value.trim().to_string();
+            if name.eq_ignore_ascii_case("etag") {
+                self.etag = Some(value);
+            } else if name.eq_ignore_ascii_case("last-modified") {
+                self.last_modified = Some(value);
+            }
+        }
+    }
+}
+
+fn get_headers_path(file_name: &Path) -> PathBuf {
+    let mut headers_name = file_name.as_os_str().to_os_string();
+    headers_name.push(HEADERS_FILE_SUFFIX);
+    PathBuf::from(headers_name)
+}
+
+fn load_cache_validators(file_name: &Path, url: &str) -> CacheValidators {
+
+    // a broken local copy must be downloaded in full again
+    let mut validators = CacheValidators::default();
+    let cache_ok = fs::read(file_name).is_ok_and(|data| validate_rates_json(&data).is_ok());
+    if !cache_ok {
+        return validators;
+    }
+
+    let json: Value = match fs::read(get_headers_path(file_name)).ok().and_then(|data| serde_json::from_slice(&data).ok()) {
+        Some(json) => json,
+        None => return validators,
+    };
+    // a copy from another --url says nothing about this one
+    if json.get("url").and_then(|stored| stored.as_str()) != Some(url) {
+        verbose!("the cached copy came from another url, downloading it in full");
+        return validators;
+    }
+    validators.etag = json.get("etag").and_then(|etag| etag.as_str()).map(String::from);
+    validators.last_modified = json.get("last_modified").and_then(|date| date.as_str()).map(String::from);
+
+    validators
+
+}
+
+fn save_cache_validators(file_name: &Path, validators: &CacheValidators) {
+
+    let headers_name = get_headers_path(file_name);
+    if validators.etag.is_none() && validators.last_modified.is_none() {
+        let _ = fs::remove_file(&headers_name);
+        return;
+    }
+
+    let json = serde_json::json!({
+        "etag": validators.etag,
+        "last_modified": validators.last_modified,
+        "url": validators.url,
+    });
+    if let Err(err) = fs::write(&headers_name, json.to_string()) {
+        eprintln!("Couldn't write {} (error: {}).", headers_name.display(), err);
+    }
+
+}
+
+fn set_timeouts(handle: &mut Easy, timeout: u64) {
+    // a stalled connection would otherwise hang forever
+    let connect_timeout = CONNECT_TIMEOUT.min(timeout);
+    if let Err(err) = handle.connect_timeout(Duration::from_secs(connect_timeout)) {
+        eprintln!("Couldn't set the connect timeout (error: {}).", err);
+    }
+    if let Err(err) = handle.timeout(Duration::from_secs(timeout)) {
+        eprintln!("Couldn't set the timeout (error: {}).", err);
+    }
+}
+
+fn set_compression(handle: &mut Easy) {
+    // curl decompresses before the write function sees the data
+    if !curl::Version::get().feature_libz() {
+        verbose!("curl is built without zlib, downloading uncompressed");
+        return;
+    }
+    if let Err(err) = handle.accept_encoding("gzip, deflate") {
+        eprintln!("Couldn't enable compression (error: {}).", err);
+    }
+}
+
+/// The message of an error envelope like {"error": "rate limited"} or
+/// {"error": {"info": "..."}}.
+fn upstream_error(json: &Value) -> Option<String> {
+    let error = json.get("error")?;
+    match error {
+        Value::String(message) => Some(message.to_string()),
+        Value::Object(object) => ["message", "info", "type"].iter()
+            .find_map(|key| object.get(*key).and_then(|message| message.as_str()))
+            .map(String::from)
+            .or_else(|| Some(error.to_string())),
+        Value::Bool(false) | Value::Null => None,
+        _ => Some(error.to_string()),
+    }
+}
+
+fn validate_rates_json(data: &[u8]) -> Result<(), String> {
+
+    // a minimal schema: {"rates": {"<code>": <number>, ...}, ...}
+    let json: Value = serde_json::from_slice(data)
+        .map_err(|err| format!("not valid JSON ({})", err))?;
+
+    let object = json.as_object()
+        .ok_or("the top level is not an object")?;
+
+    if let Some(message) = upstream_error(&json) {
+        return Err(format!("the provider answered with an error: {}", message));
+    }
+
+    if object.get("success").and_then(|success| success.as_bool()) == Some(false) {
+        return Err(String::from("the provider marked the answer as unsuccessful"));
+    }
+
+    let rates = object.get("rates")
+        .ok_or("there is no 'rates' member")?
+        .as_object()
+        .ok_or("'rates' is not an object")?;
+
+    if rates.is_empty() {
+        return Err(String::from("'rates' is empty"));
+    }
+
+    if let Some((key, _)) = rates.iter().find(|(_, val)| !val.is_number()) {
+        return Err(format!("the rate of '{}' is not a number", key));
+    }
+
+    Ok(())
+
+}
+
+/// The proxy for `url`: the --proxy flag, else the usual environment
+/// variables unless the host is listed in NO_PROXY.
+fn set_proxy(handle: &mut Easy, url: &str, proxy: Option<&str>) -> Option<String> {
+
+    let proxy = match proxy {
+        Some(proxy) => proxy.to_string(),
+        None => {
+            let names: &[&str] = if url.starts_with("https://") {
+                &["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"]
+            } else {
+                &["http_proxy", "HTTP_PROXY", "ALL_PROXY", "all_proxy"]
+            };
+            let proxy = names.iter()
+                .filter_map(|name| env::var(name).ok())
+                .find(|value| !value.is_empty())?;
+            if is_no_proxy_host(url) {
+                verbose!("not using the proxy {} for {}, see NO_PROXY", proxy, url);
+                return None;
+            }
+            proxy
+        }
+    };
+
+    verbose!("using the proxy {}", proxy);
+    if let Err(err) = handle.proxy(&proxy) {
+        eprintln!("Couldn't set the proxy {} (error: {}).", proxy, err);
+    }
+
+    Some(proxy)
+
+}
+
+fn is_no_proxy_host(url: &str) -> bool {
+
+    let no_proxy = match ["NO_PROXY", "no_proxy"].iter().find_map(|name| env::var(name).ok()) {
+        Some(no_proxy) => no_proxy,
+        None => return false,
+    };
+
+    // "https://host:port/path" -> "host"
+    let host = url.split("://").nth(1).unwrap_or(url);
+    let host = host.split(['/', ':']).next().unwrap_or(host).to_ascii_lowercase();
+
+    no_proxy.split(',')
+        .map(|entry| entry.trim().trim_start_matches('.').to_ascii_lowercase())
+        .filter(|entry| !entry.is_empty())
+        .any(|entry| entry == "*" || host == entry || host.ends_with(&format!(".{}", entry)))
+
+}
+
+fn report_transfer_error(err: &curl::Error, proxy: Option<&str>) {
+    // tell proxy trouble apart from an outage of the provider
+    match proxy {
+        Some(proxy) if err.is_couldnt_resolve_proxy() => eprintln!("Couldn't resolve the proxy {}: {}", proxy, err),
+        Some(proxy) => eprintln!("Error while download through the proxy {}: {}", proxy, err),
+        None => eprintln!("Error while download: {}", err),
+    }
+}
+
+fn load_rates_file_from_disk(file_name: &Path, exchange_rates: &mut HashMap<String, f64>, meta: &mut RatesMeta) -> bool {
+
+    // the JSON stays the source of truth, the binary copy is only
+    // used while it is at least as new
+    let binary_name = get_binary_cache_path(file_name);
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    if let (Some(json_time), Some(binary_time)) = (modified(file_name), modified(&binary_name)) {
+        if binary_time >= json_time {
+            if let Some((rates, binary_meta)) = load_binary_cache(&binary_name) {
+                verbose!("loaded {} currencies from {}", rates.len(), binary_name.display());
+                exchange_rates.extend(rates);
+                *meta = binary_meta;
+                return true;
+            }
+            verbose!("{} is unreadable, parsing the JSON", binary_name.display());
+        }
+    }
+
+    if !load_rates_file(file_name, exchange_rates, meta) {
+        return false;
+    }
+    save_binary_cache(&binary_name, exchange_rates, meta);
+
+    true
+
+}
+
+fn get_binary_cache_path(file_name: &Path) -> PathBuf {
+    let mut binary_name = file_name.as_os_str().to_os_string();
+    binary_name.push(BINARY_CACHE_SUFFIX);
+    PathBuf::from(binary_name)
+}
+
+/// Writes the parsed rates as the magic, the optional date, base and
+/// source, the number of rates and then each code with its
+/// little-endian f64.
+fn save_binary_cache(binary_name: &Path, rates: &HashMap<String, f64>, meta: &RatesMeta) {
+
+    let mut data = BINARY_CACHE_MAGIC.to_vec();
+    for text in [&meta.as_of, &meta.base, &meta.source] {
+        match text {
+            Some(text) => {
+                data.push(1);
+                data.extend_from_slice(&(text.len() as u32).to_le_bytes());
+                data.extend_from_slice(text.as_bytes());
+            }
+            None => data.push(0),
+        }
+    }
+    data.extend_from_slice(&(rates.len() as u32).to_le_bytes());
+    for (code, rate) in rates.iter() {
+        // the codes are short, a longer one would not fit its length byte
+        let Ok(length) = u8::try_from(code.len()) else {
+            return;
+        };
+        data.push(length);
+        data.extend_from_slice(code.as_bytes());
+        data.extend_from_slice(&rate.to_le_bytes());
+    }
+
+    // the binary copy is optional, a partial one must not stay behind
+    if let Err(err) = fs::write(binary_name, data) {
+        verbose!("couldn't write {} (error: {})", binary_name.display(), err);
+        let _ = fs::remove_file(binary_name);
+    }
+
+}
+
+fn load_binary_cache(binary_name: &Path) -> Option<(HashMap<String, f64>, RatesMeta)> {
+
+    let data = fs::read(binary_name).ok()?;
+    let mut rest = data.strip_prefix(BINARY_CACHE_MAGIC)?;
+
+    let mut take = |count: usize| -> Option<&[u8]> {
+        if rest.len() < count {
+            return None;
+        }
+        let (head, tail) = rest.split_at(count);
+        rest = tail;
+        Some(head)
+    };
+
+    let mut texts = Vec::new();
+    for _ in 0..3 {
+        texts.push(match take(1)?[0] {
+            0 => None,
+            _ => {
+                let length = u32::from_le_bytes(take(4)?.try_into().ok()?) as usize;
+                Some(String::from_utf8(take(length)?.to_vec()).ok()?)
+            }
+        });
+    }
+    let mut texts = texts.into_iter();
+    let meta = RatesMeta {
+        as_of: texts.next()?,
+        base: texts.next()?,
+        source: texts.next()?,
+    };
+
+    let count = u32::from_le_bytes(take(4)?.try_into().ok()?) as usize;
+    let mut rates = HashMap::new();
+    for _ in 0..count {
+        let length = take(1)?[0] as usize;
+        let code = String::from_utf8(take(length)?.to_vec()).ok()?;
+        let rate = f64::from_le_bytes(take(8)?.try_into().ok()?);
+        rates.insert(code, rate);
+    }
+
+    Some((rates, meta))
+
+}
+
+fn load_rates_file(file_name: &Path, exchange_rates: &mut HashMap<String, f64>, meta: &mut RatesMeta) -> bool {
+
+    // read it in one piece, the provider may pretty-print its JSON
+    let content = match fs::read_to_string(file_name) {
+        Ok(content) => content,
+        Err(err) => {
+            eprintln!("Couldn't open {} (error: {}).", file_name.display(), err);
+            return false
+        },
+    };
+
+    if content.trim().is_empty() {
+        eprintln!("File is empty.");
+        return false;
+    }
+
+    let json: Value = match serde_json::from_str(&content) {
+        Ok(json) => json,
+        Err(err) => {
+            eprintln!("Couldn't parse {} (error: {}).", file_name.display(), err);
+            return false
+        },
+    };
+    if json.get("success").and_then(|success| success.as_bool()) == Some(false) {
+        match upstream_error(&json) {
+            Some(message) => eprintln!("{} is marked as unsuccessful, the provider answered: {}", file_name.display(), message),
+            None => eprintln!("{} is marked as unsuccessful.", file_name.display()),
+        }
+        return false;
+    }
+    let rates = match json.get("rates").and_then(|rates| rates.as_object()) {
+        Some(rates) => rates,
+        None => {
+            match upstream_error(&json) {
+                Some(message) => eprintln!("{} did not contain a 'rates' object, the provider answered: {}", file_name.display(), message),
+                None => eprintln!("{} did not contain a 'rates' object.", file_name.display()),
+            }
+            return false
+        },
+    };
+
+    for rate in rates.iter() {
+        let key: String = rate.0.to_string();
+        let val: f64 = match rate.1.as_f64() {
+            Some(val) => val,
+            None => {
+                eprintln!("Warning: skipping {}, its rate {} is not a number.", key, rate.1);
+                continue
+            },
+        };
+        exchange_rates.insert(key, val);
+    }
+
+    verbose!("loaded {} currencies from {}", exchange_rates.len(), file_name.display());
+
+    // some providers leave their base currency out of the table
+    if let Some(base) = json.get("base").and_then(|base| base.as_str()) {
+        let rate = *exchange_rates.entry(base.to_string()).or_insert(1.0);
+        if (rate - 1.0).abs() > ROUND_TRIP_TOLERANCE {
+            eprintln!("Warning: {} names {} as its base, but its rate is {}.", file_name.display(), base, rate);
+        }
+        meta.base = Some(base.to_string());
+    }
+    meta.source = json.get("source").and_then(|source| source.as_str()).map(String::from);
+
+    // prefer the exact timestamp over the plain date
+    if let Some(timestamp) = json.get("timestamp").and_then(|timestamp| timestamp.as_u64()) {
+        meta.as_of = Some(format_timestamp(timestamp));
+    } else if let Some(date) = json.get("date").and_then(|date| date.as_str()) {
+        meta.as_of = Some(format!("{} (UTC)", date));
+    }
+
+    true
+}
+
+/// Loads the user's own currency names, a JSON object like
+/// {"XAU": "Gold"} in names.json next to the cached rates.
+fn load_currency_names() -> HashMap<String, String> {
+
+    let mut names = HashMap::new();
+
+    let file_name = Path::new(get_temp_dir().as_str()).join(NAMES_FILENAME);
+    let content = match fs::read_to_string(&file_name) {
+        Ok(content) => content,
+        Err(_) => return names,
+    };
+
+    let json: Value = match serde_json::from_str(&content) {
+        Ok(json) => json,
+        Err(err) => {
+            eprintln!("Ignoring {}, couldn't parse it (error: {}).", file_name.display(), err);
+            return names
+        },
+    };
+
+    match json.as_object() {
+        Some(object) => {
+            for (code, name) in object.iter() {
+                match name.as_str() {
+                    Some(name) => {
+                        names.insert(code.to_ascii_uppercase(), name.to_string());
+                    }
+                    None => eprintln!("Warning: skipping {} in {}, its name is not a string.", code, file_name.display()),
+                }
+            }
+        }
+        None => eprintln!("Ignoring {}, it is not a JSON object.", file_name.display()),
+    }
+
+    names
+
+}
+
+/// The user's name for the currency if there is one, otherwise the
+/// built-in name from `get_currency_name`.
+fn lookup_currency_name(names: &HashMap<String, String>, currency: &str) -> String {
+    match names.get(currency) {
+        Some(name) => name.to_string(),
+        None => get_currency_name(currency),
+    }
+}
+
+pub fn get_currency_name(currency: &str) -> String {
+    match currency {
+        "AED" => "United Arab Emirates Dirham".to_string(),
+        "AFN" => "Afghan Afghani".to_string(),
+        "ALL" => "Albanian Lek".to_string(),
+        "AMD" => "Armenian Dram".to_string(),
+        "ANG" => "Netherlands Antillean Guilder".to_string(),
+        "AOA" => "Angolan Kwanza".to_string(),
+        "ARS" => "Argentine Peso".to_string(),
+        "AUD" => "Australian Dollar".to_string(),
+        "AWG" => "Aruban Florin".to_string(),
+        "AZN" => "Azerbaijani Manat".to_string(),
+        "BAM" => "Bosnia-Herzegovina Convertible Mark".to_string(),
+        "BBD" => "Barbadian Dollar".to_string(),
+        "BDT" => "Bangladeshi Taka".to_string(),
+        "BGN" => "Bulgarian Lev".to_string(),
+        "BHD" => "Bahraini Dinar".to_string(),
+        "BIF" => "Burundian Franc".to_string(),
+        "BMD" => "Bermudian Dollar".to_string(),
+        "BND" => "Brunei Dollar".to_string(),
+        "BOB" => "Bolivian Boliviano".to_string(),
+        "BRL" => "Brazilian Real".to_string(),
+        "BSD" => "Bahamian Dollar".to_string(),
+        "BTN" => "Bhutanese Ngultrum".to_string(),
+        "BWP" => "Botswana Pula".to_string(),
+        "BYN" => "Belarusian Ruble".to_string(),
+        "BZD" => "Belize Dollar".to_string(),
+        "CAD" => "Canadian Dollar".to_string(),
+        "CDF" => "Congolese Franc".to_string(),
+        "CHF" => "Swiss Franc".to_string(),
+        "CLF" => "Chilean Unit of Account (UF)".to_string(),
+        "CLP" => "Chilean Peso".to_string(),
+        "CNY" => "Chinese Yuan Renminbi".to_string(),
+        "COP" => "Colombian Peso".to_string(),
+        "CRC" => "Costa Rican Colon".to_string(),
+        "CUP" => "Cuban Peso".to_string(),
+        "CVE" => "Cape Verdean Escudo".to_string(),
+        "CZK" => "Czech Koruna".to_string(),
+        "DJF" => "Djiboutian Franc".to_string(),
+        "DKK" => "Danish Krone".to_string(),
+        "DOP" => "Dominican Peso".to_string(),
+        "DZD" => "Algerian Dinar".to_string(),
+        "EGP" => "Egyptian Pound".to_string(),
+        "ERN" => "Eritrean Nakfa".to_string(),
+        "ETB" => "Ethiopian Birr".to_string(),
+        "EUR" => "Euro".to_string(),
+        "FJD" => "Fijian Dollar".to_string(),
+        "FKP" => "Falkland Islands Pound".to_string(),
+        "GBP" => "Pound Sterling".to_string(),
+        "GEL" => "Georgian Lari".to_string(),
+        "GHS" => "Ghanaian Cedi".to_string(),
+        "GIP" => "Gibraltar Pound".to_string(),
+        "GMD" => "Gambian Dalasi".to_string(),
+        "GNF" => "Guinean Franc".to_string(),
+        "GTQ" => "Guatemalan Quetzal".to_string(),
+        "GYD" => "Guyanese Dollar".to_string(),
+        "HKD" => "Hong Kong Dollar".to_string(),
+        "HNL" => "Honduran Lempira".to_string(),
+        "HTG" => "Haitian Gourde".to_string(),
+        "HUF" => "Hungarian Forint".to_string(),
+        "IDR" => "Indonesian Rupiah".to_string(),
+        "ILS" => "Israeli Shekel".to_string(),
+        "INR" => "Indian Rupee".to_string(),
+        "IQD" => "Iraqi Dinar".to_string(),
+        "IRR" => "Iranian Rial".to_string(),
+        "ISK" => "Icelandic Krona".to_string(),
+        "JMD" => "Jamaican Dollar".to_string(),
+        "JOD" => "Jordanian Dinar".to_string(),
+        "JPY" => "Japanese Yen".to_string(),
+        "KES" => "Kenyan Shilling".to_string(),
+        "KGS" => "Kyrgyzstani Som".to_string(),
+        "KHR" => "Cambodian Riel".to_string(),
+        "KMF" => "Comorian Franc".to_string(),
+        "KPW" => "North Korean Won".to_string(),
+        "KRW" => "South Korean Won".to_string(),
+        "KWD" => "Kuwaiti Dinar".to_string(),
+        "KYD" => "Cayman Islands Dollar".to_string(),
+        "KZT" => "Kazakhstani Tenge".to_string(),
+        "LAK" => "Lao Kip".to_string(),
+        "LBP" => "Lebanese Pound".to_string(),
+        "LKR" => "Sri Lankan Rupee".to_string(),
+        "LRD" => "Liberian Dollar".to_string(),
+        "LSL" => "Lesotho Loti".to_string(),
+        "LYD" => "Libyan Dinar".to_string(),
+        "MAD" => "Moroccan Dirham".to_string(),
+        "MDL" => "Moldovan Leu".to_string(),
+        "MGA" => "Malagasy Ariary".to_string(),
+        "MKD" => "Macedonian Denar".to_string(),
+        "MMK" => "Myanmar Kyat".to_string(),
+        "MNT" => "Mongolian Tugrik".to_string(),
+        "MOP" => "Macanese Pataca".to_string(),
+        "MRU" => "Mauritanian Ouguiya".to_string(),
+        "MUR" => "Mauritian Rupee".to_string(),
+        "MVR" => "Maldivian Rufiyaa".to_string(),
+        "MWK" => "Malawian Kwacha".to_string(),
+        "MXN" => "Mexican Peso".to_string(),
+        "MYR" => "Malaysian Ringgit".to_string(),
+        "MZN" => "Mozambican Metical".to_string(),
+        "NAD" => "Namibian Dollar".to_string(),
+        "NGN" => "Nigerian Naira".to_string(),
+        "NIO" => "Nicaraguan Cordoba".to_string(),
+        "NOK" => "Norwegian Krone".to_string(),
+        "NPR" => "Nepalese Rupee".to_string(),
+        "NZD" => "New Zealand Dollar".to_string(),
+        "OMR" => "Omani Rial".to_string(),
+        "PAB" => "Panamanian Balboa".to_string(),
+        "PEN" => "Peruvian Sol".to_string(),
+        "PGK" => "Papua New Guinean Kina".to_string(),
+        "PHP" => "Philippine Peso".to_string(),
+        "PKR" => "Pakistani Rupee".to_string(),
+        "PLN" => "Polish Zloty".to_string(),
+        "PYG" => "Paraguayan Guarani".to_string(),
+        "QAR" => "Qatari Riyal".to_string(),
+        "RON" => "Romanian Leu".to_string(),
+        "RSD" => "Serbian Dinar".to_string(),
+        "RUB" => "Russian Ruble".to_string(),
+        "RWF" => "Rwandan Franc".to_string(),
+        "SAR" => "Saudi Riyal".to_string(),
+        "SBD" => "Solomon Islands Dollar".to_string(),
+        "SCR" => "Seychellois Rupee".to_string(),
+        "SDG" => "Sudanese Pound".to_string(),
+        "SEK" => "Swedish Krona".to_string(),
+        "SGD" => "Singapore Dollar".to_string(),
+        "SHP" => "Saint Helena Pound".to_string(),
+        "SLE" => "Sierra Leonean Leone".to_string(),
+        "SOS" => "Somali Shilling".to_string(),
+        "SRD" => "Surinamese Dollar".to_string(),
+        "SSP" => "South Sudanese Pound".to_string(),
+        "STN" => "Sao Tome and Principe Dobra".to_string(),
+        "SVC" => "Salvadoran Colon".to_string(),
+        "SYP" => "Syrian Pound".to_string(),
+        "SZL" => "Swazi Lilangeni".to_string(),
+        "THB" => "Thai Baht".to_string(),
+        "TJS" => "Tajikistani Somoni".to_string(),
+        "TMT" => "Turkmenistani Manat".to_string(),
+        "TND" => "Tunisian Dinar".to_string(),
+        "TOP" => "Tongan Pa'anga".to_string(),
+        "TRY" => "Turkish Lira".to_string(),
+        "TTD" => "Trinidad and Tobago Dollar".to_string(),
+        "TWD" => "New Taiwan Dollar".to_string(),
+        "TZS" => "Tanzanian Shilling".to_string(),
+        "UAH" => "Ukrainian Hryvnia".to_string(),
+        "UGX" => "Ugandan Shilling".to_string(),
+        "USD" => "US Dollar".to_string(),
+        "UYU" => "Uruguayan Peso".to_string(),
+        "UZS" => "Uzbekistani Som".to_string(),
+        "VES" => "Venezuelan Bolivar".to_string(),
+        "VND" => "Vietnamese Dong".to_string(),
+        "VUV" => "Vanuatu Vatu".to_string(),
+        "WST" => "Samoan Tala".to_string(),
+        "XAF" => "Central African CFA Franc".to_string(),
+        "XAG" => "Silver (troy ounce)".to_string(),
+        "XAU" => "Gold (troy ounce)".to_string(),
+        "XCD" => "East Caribbean Dollar".to_string(),
+        "XDR" => "IMF Special Drawing Rights".to_string(),
+        "XOF" => "West African CFA Franc".to_string(),
+        "XPD" => "Palladium (troy ounce)".to_string(),
+        "XPF" => "CFP Franc".to_string(),
+        "XPT" => "Platinum (troy ounce)".to_string(),
+        "YER" => "Yemeni Rial".to_string(),
+        "ZAR" => "South African Rand".to_string(),
+        "ZMW" => "Zambian Kwacha".to_string(),
+        "ZWL" => "Zimbabwean Dollar".to_string(),
+        _ => String::from("Unknown"),
+    }
+}
+
+/// The usual symbol of the currency, if it has one that differs from
+/// its code.
+pub fn get_currency_symbol(currency: &str) -> Option<&'static str> {
+    match currency {
+        "EUR" => Some("€"),
+        "USD" => Some("$"),
+        "GBP" => Some("£"),
+        "JPY" => Some("¥"),
+        "CNY" => Some("¥"),
+        "CHF" => Some("Fr."),
+        "AUD" => Some("$"),
+        "CAD" => Some("$"),
+        "NZD" => Some("$"),
+        "HKD" => Some("$"),
+        "SGD" => Some("$"),
+        "MXN" => Some("$"),
+        "BRL" => Some("R$"),
+        "INR" => Some("₹"),
+        "KRW" => Some("₩"),
+        "RUB" => Some("₽"),
+        "TRY" => Some("₺"),
+        "ILS" => Some("₪"),
+        "PLN" => Some("zł"),
+        "CZK" => Some("Kč"),
+        "HUF" => Some("Ft"),
+        "SEK" => Some("kr"),
+        "NOK" => Some("kr"),
+        "DKK" => Some("kr"),
+        "ISK" => Some("kr"),
+        "THB" => Some("฿"),
+        "PHP" => Some("₱"),
+        "VND" => Some("₫"),
+        "UAH" => Some("₴"),
+        "NGN" => Some("₦"),
+        "ZAR" => Some("R"),
+        "IDR" => Some("Rp"),
+        "MYR" => Some("RM"),
+        "BGN" => Some("лв"),
+        "RON" => Some("lei"),
+        _ => None,
+    }
+}
+
+/// Number of decimal places (ISO 4217 minor units) the currency is
+/// conventionally written with.
+pub fn minor_units(currency: &str) -> u8 {
+    match currency {
+        "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" |
+        "RWF" | "UGX" | "UYI" | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+        "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+        "CLF" | "UYW" => 4,
+        _ => 2,
+    }
+}
+
+/// The region of a currency code, Other for metals, crypto and codes
+/// that aren't classified.
+pub fn region(currency: &str) -> Region {
+    match currency {
+        "ALL" | "AMD" | "AZN" | "BAM" | "BGN" | "BYN" | "CHF" | "CZK" | "DKK" | "EUR" |
+        "GBP" | "GEL" | "GIP" | "HUF" | "ISK" | "MDL" | "MKD" | "NOK" | "PLN" | "RON" |
+        "RSD" | "RUB" | "SEK" | "TRY" | "UAH" => Region::Europe,
+        "ARS" | "BBD" | "BMD" | "BOB" | "BRL" | "BSD" | "BZD" | "CAD" | "CLP" | "COP" |
+        "CRC" | "CUP" | "DOP" | "GTQ" | "GYD" | "HNL" | "HTG" | "JMD" | "KYD" | "MXN" |
+        "NIO" | "PAB" | "PEN" | "PYG" | "SRD" | "TTD" | "USD" | "UYU" | "VES" | "XCD" => Region::Americas,
+        "AFN" | "AUD" | "BDT" | "BND" | "BTN" | "CNY" | "FJD" | "HKD" | "IDR" | "INR" |
+        "JPY" | "KGS" | "KHR" | "KRW" | "KZT" | "LAK" | "LKR" | "MMK" | "MNT" | "MOP" |
+        "MVR" | "MYR" | "NPR" | "NZD" | "PGK" | "PHP" | "PKR" | "SGD" | "THB" | "TJS" |
+        "TMT" | "TWD" | "UZS" | "VND" | "WST" | "XPF" => Region::AsiaPacific,
+        "AED" | "AOA" | "BHD" | "BIF" | "BWP" | "CDF" | "DZD" | "EGP" | "ETB" | "GHS" |
+        "GMD" | "GNF" | "ILS" | "IQD" | "IRR" | "JOD" | "KES" | "KWD" | "LBP" | "LYD" |
+        "MAD" | "MGA" | "MUR" | "MWK" | "MZN" | "NAD" | "NGN" | "OMR" | "QAR" | "RWF" |
+        "SAR" | "SCR" | "SDG" | "SYP" | "TND" | "TZS" | "UGX" | "XAF" | "XOF" | "YER" |
+        "ZAR" | "ZMW" => Region::AfricaMiddleEast,
+        _ => Region::Other,
+    }
+}
+
+fn find_provider(name: &str) -> Option<Provider> {
+    PROVIDERS.iter().find(|provider| provider.name.eq_ignore_ascii_case(name)).copied()
+}
+
+fn get_rates_file_path(provider: &Provider) -> PathBuf {
+    // every provider gets its own cache, so switching between them
+    // never mixes their data
+    let file_name = if *provider == DEFAULT_PROVIDER {
+        DEFAULT_FILENAME.to_string()
+    } else {
+        format!("currency-{}.json", provider.name)
+    };
+    Path::new(get_temp_dir().as_str()).join(file_name)
+}
+
+/// The per-user cache directory, $XDG_CACHE_HOME/currency-calc or
+/// ~/.cache/currency-calc, created if needed.
+#[cfg(target_os="linux")]
+fn get_xdg_cache_dir() -> Option<String> {
+
+    let xdg_cache = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute());
+    let home_cache = env::var_os("HOME")
+        .map(|home| Path::new(&home).join(".cache"));
+
+    for dir in [xdg_cache, home_cache].into_iter().flatten() {
+        let dir = dir.join("currency-calc");
+        if fs::create_dir_all(&dir).is_ok() {
+            return Some(dir.to_string_lossy().into_owned());
+        }
+    }
+
+    None
+
+}
+
+/// The directory from CURRENCY_CALC_CACHE_DIR, used as given.
+fn get_cache_dir_override() -> Option<String> {
+    env::var("CURRENCY_CALC_CACHE_DIR").ok().filter(|dir| !dir.is_empty())
+}
+
+/// Fails with a clear message when the CURRENCY_CALC_CACHE_DIR
+/// directory can't be created or written.
+fn check_cache_dir_override() -> bool {
+
+    let dir = match get_cache_dir_override() {
+        Some(dir) => PathBuf::from(dir),
+        None => return true,
+    };
+
+    if let Err(err) = fs::create_dir_all(&dir) {
+        eprintln!("Couldn't create the cache directory {} from CURRENCY_CALC_CACHE_DIR (error: {}).", dir.display(), err);
+        return false;
+    }
+
+    let probe = dir.join(format!(".write-test-{}", std::process::id()));
+    if let Err(err) = File::create(&probe) {
+        eprintln!("The cache directory {} from CURRENCY_CALC_CACHE_DIR is not writable (error: {}).", dir.display(), err);
+        return false;
+    }
+    let _ = fs::remove_file(&probe);
+
+    true
+
+}
+
+fn get_temp_dir() -> String {
+    if let Some(dir) = get_cache_dir_override() {
+        return dir;
+    }
+    #[cfg(target_os="windows")]
+    let d = env::var("TEMP").unwrap_or_else(|err| {
+        eprintln!("could not find %TEMP%: {}", err);
+        String::from(".")
+    });
+    #[cfg(target_os="linux")]
+    let d = get_xdg_cache_dir().unwrap_or_else(|| String::from("/tmp"));
+    #[cfg(target_os="macos")]
+    let d = env::var("TMPDIR").unwrap_or_else(|_| String::from("/tmp"));
+    #[cfg(not(any(target_os="windows", target_os="linux", target_os="macos")))]
+    let d = env::temp_dir().to_string_lossy().into_owned();
+    d
+}
+
+/// $XDG_CONFIG_HOME/currency-calc/config.toml, ~/.config/... without
+/// it, or %APPDATA%\currency-calc\config.toml on Windows.
+fn get_config_path() -> Option<PathBuf> {
+    #[cfg(target_os="windows")]
+    let dir = env::var_os("APPDATA").map(PathBuf::from);
+    #[cfg(not(target_os="windows"))]
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")));
+    dir.map(|dir| dir.join("currency-calc").join(CONFIG_FILENAME))
+}
+
+/// Reads the defaults from the config file, e.g.
+///
+/// ```toml
+/// default_from = "EUR"
+/// default_to = "USD"
+/// default_precision = 2
+/// ```
+///
+/// Only this flat `key = value` subset of TOML is understood.
+fn load_config(exchange: &mut ExchangeProcess, options: &mut Options) {
+
+    let file_name = match get_config_path() {
+        Some(file_name) => file_name,
+        None => return,
+    };
+    let content = match fs::read_to_string(&file_name) {
+        Ok(content) => content,
+        Err(_) => return,
+    };
+
+    for (index, line) in content.lines().enumerate() {
+
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), parse_config_value(value)),
+            None => {
+                eprintln!("Ignoring line {} of {}, expected key = value.", index + 1, file_name.display());
+                continue;
+            }
+        };
+
+        match key {
+            "default_from" | "default_to" if looks_like_currency(&value) => {
+                let currency = Some(value.to_ascii_uppercase());
+                if key == "default_from" {
+                    options.default_from = currency;
+                } else {
+                    options.default_to = currency;
+                }
+            }
+            "usual" => match parse_currency_list(&value) {
+                Ok(codes) => options.usual = Some(codes),
+                Err(err) => eprintln!("Ignoring invalid usual in {}: {}", file_name.display(), err),
+            },
+            "default_precision" => match value.parse::<usize>() {
+                Ok(precision) if precision <= MAX_PRECISION => exchange.precision = Some(precision),
+                _ => eprintln!("Ignoring invalid default_precision in {}: {}", file_name.display(), value),
+            },
+            "default_from" | "default_to" => eprintln!("Ignoring invalid {} in {}: {}", key, file_name.display(), value),
+            _ => eprintln!("Ignoring unknown key {} in {}.", key, file_name.display()),
+        }
+
+    }
+
+}
+
+/// A comma separated list of currency codes like "USD,EUR,GBP",
+/// upper cased and without duplicates.
+fn parse_currency_list(value: &str) -> Result<Vec<String>, String> {
+
+    let mut codes: Vec<String> = Vec::new();
+
+    for code in value.split(',').map(str::trim).filter(|code| !code.is_empty()) {
+        check_currency_code(code)?;
+        let code = code.to_ascii_uppercase();
+        if !codes.contains(&code) {
+            codes.push(code);
+        }
+    }
+
+    if codes.is_empty() {
+        return Err("Expected a comma separated list of currencies, e.g. USD,EUR,GBP.".to_string());
+    }
+
+    Ok(codes)
+}
+
+/// The value of a `key = value` line: a "quoted string" or a bare
+/// value, both without a trailing comment.
+fn parse_config_value(value: &str) -> String {
+    let value = value.trim();
+    match value.strip_prefix('"').and_then(|rest| rest.split_once('"')) {
+        Some((quoted, _)) => quoted.to_string(),
+        None => value.split('#').next().unwrap_or_default().trim().to_string(),
+    }
+}
+
+/// Parses `args`, the program name first like `env::args()`, into
+/// `exchange` and `options`. It never exits the process, help, version
+/// and missing arguments are returned for `run()` to handle.
+fn parse_arguments(args: &[String], exchange: &mut ExchangeProcess, options: &mut Options) -> ArgumentResult {
+
+    let prg_name = args.first().map(String::as_str).unwrap_or_default();
+
+    if args.len() < 2 {
+        println!("{} needs three arguments or try --help.", prg_name);
+        return ArgumentResult::MissingArguments;
+    }
+
+    // a subcommand is turned into its flag, so there is only one parser
+    let (subcommand, params) = split_subcommand(&args[1..]);
+    let mut params = params.into_iter();
+
+    // the config file comes first, the environment and the
+    // arguments override it
+    load_config(exchange, options);
+
+    if let Ok(value) = env::var("CURRENCY_CALC_MAX_AGE") {
+        match value.parse::<u64>() {
+            Ok(max_age) => options.max_age = max_age,
+            Err(_) => eprintln!("Ignoring invalid CURRENCY_CALC_MAX_AGE: {}", value),
+        }
+    }
+
+    if let Ok(url) = env::var("CURRENCY_CALC_URL") {
+        if is_valid_url(&url) {
+            options.url = Some(url);
+        } else {
+            eprintln!("Ignoring invalid CURRENCY_CALC_URL: {}", url);
+        }
+    }
+
+    if let Ok(key) = env::var("CURRENCY_CALC_API_KEY") {
+        if !key.is_empty() {
+            options.api_key = Some(key);
+        }
+    }
+
+    let mut stdin_amount = false;
+    let mut interactive = false;
+    let mut batch = false;
+    let mut rate_only = false;
+    let mut cents = false;
+    let mut precision_given = false;
+    let mut round_given = false;
+    let mut list = None;
+    let mut positional_only = false;
+    let mut positionals: Vec<String> = Vec::new();
+    while let Some(param) = params.next() {
+
+        // everything after "--" is positional, e.g. a negative amount
+        let flag = if positional_only { "" } else { &param[..] };
+        match flag {
+
+            "--" => {
+                positional_only = true;
+            }
+
+            "-h" | "--help" => {
+                return ArgumentResult::ShowHelp;
+            }
+
+            "--completions" => {
+                return ArgumentResult::ShowCompletions(params.next().unwrap_or_default());
+            }
+
+            "-V" | "--version" => {
+                return ArgumentResult::ShowVersion;
+            }
+
+            "-lu" | "--list-usual" | "-l" | "--list" | "-la" | "--list-all" | "--audit-names" | "--health" | "--diff" => {
+                let mode = match flag {
+                    "-la" | "--list-all" => ArgumentResult::SuccessAndCompleteList,
+                    "--audit-names" => ArgumentResult::SuccessAndAuditNames,
+                    "--health" => ArgumentResult::SuccessAndHealth,
+                    "--diff" => ArgumentResult::SuccessAndDiff,
+                    _ => ArgumentResult::SuccessAndUsualList,
+                };
+                if let Some(previous) = &list {
+                    if std::mem::discriminant(previous) != std::mem::discriminant(&mode) {
+                        eprintln!("Only one of --list-usual, --list-all, --audit-names, --health and --diff can be used.");
+                        return ArgumentResult::ArgumentError;
+                    }
+                }
+                list = Some(mode);
+            }
+
+            "--all-targets" => {
+                options.all_targets = true;
+            }
+
+            "--grouped" => {
+                options.grouped = true;
+            }
+
+            "--copy" => {
+                options.copy = true;
+            }
+
+            "--rank" => {
+                options.rank = true;
+            }
+
+            "--rate" => {
+                rate_only = true;
+            }
+
+            "--round-trip" => {
+                options.round_trip = true;
+            }
+
+            "--spread" => {
+                options.spread = options.spread.or(Some(DEFAULT_SPREAD_BAND));
+            }
+
+            "--percent-of" => {
+                let amount = params.next().unwrap_or_default();
+                let currency = params.next().unwrap_or_default();
+                let amount = match parse_amount(&amount) {
+                    Ok(amount) => amount,
+                    Err(err) => {
+                        eprintln!("--percent-of needs an amount and a currency, e.g. 100 USD ({})", err);
+                        return ArgumentResult::ArgumentError;
+                    }
+                };
+                // nothing is a percentage of zero
+                if amount == 0.0 {
+                    eprintln!("The amount of --percent-of can't be 0.");
+                    return ArgumentResult::ArgumentError;
+                }
+                if let Err(err) = check_currency_code(&currency) {
+                    eprintln!("{}", err);
+                    return ArgumentResult::ArgumentError;
+                }
+                options.percent_of = Some((amount, currency.to_ascii_uppercase()));
+            }
+
+            "--spread-band" => {
+                let value = params.next().unwrap_or_default();
+                let band = value.split_once(',')
+                    .and_then(|(low, high)| Some((low.trim().parse::<f64>().ok()?, high.trim().parse::<f64>().ok()?)));
+                match band {
+                    Some((low, high)) if 0.0 <= low && low <= high && high < 100.0 => options.spread = Some((low, high)),
+                    _ => {
+                        eprintln!("Invalid spread band: {} (use LOW,HIGH in percent, e.g. 1,2)", value);
+                        return ArgumentResult::ArgumentError;
+                    }
+                }
+            }
+
+            "--search" => {
+                options.search = Some(params.next().unwrap_or_default());
+            }
+
+            "--stdin-amount" => {
+                stdin_amount = true;
+            }
+
+            "-i" | "--interactive" => {
+                interactive = true;
+            }
+
+            "--batch" | "-" => {
+                batch = true;
+            }
+
+            "--verify" => {
+                options.verify = true;
+            }
+
+            "--check" => {
+                options.check = true;
+            }
+
+            "--show-path" => {
+                options.show_path = true;
+            }
+
+            "-r" | "--reverse" => {
+                options.reverse = true;
+            }
+
+            "--base" => {
+                let base = params.next().unwrap_or_default();
+                if base.is_empty() {
+                    eprintln!("--base needs a currency, try: {} --help", prg_name);
+                    return ArgumentResult::ArgumentError;
+                }
+                if let Err(err) = check_currency_code(&base) {
+                    eprintln!("{}", err);
+                    return ArgumentResult::ArgumentError;
+                }
+                options.base = Some(base.to_ascii_uppercase());
+            }
+
+            "--usual" => match parse_currency_list(&params.next().unwrap_or_default()) {
+                Ok(codes) => options.usual = Some(codes),
+                Err(err) => {
+                    eprintln!("--usual: {}", err);
+                    return ArgumentResult::ArgumentError;
+                }
+            },
+
+            // rounding to the decimals of the currency is the default
+            // now, the flag stays so existing scripts keep working
+            "--smart-round" => (),
+
+            "--json" => {
+                if options.json {
+                    eprintln!("--json was given more than once.");
+                    return ArgumentResult::ArgumentError;
+                }
+                options.json = true;
+            }
+
+            "-v" | "--verbose" => {
+                VERBOSE.store(true, Ordering::Relaxed);
+            }
+
+            "-q" | "--quiet" => {
+                if options.quiet {
+                    eprintln!("--quiet was given more than once.");
+                    return ArgumentResult::ArgumentError;
+                }
+                options.quiet = true;
+            }
+
+            "--symbol" => {
+                options.symbol = true;
+            }
+
+            "--locale" => {
+                let tag = params.next().unwrap_or_default();
+                // auto takes the locale the environment uses for numbers
+                let tag = if tag == "auto" {
+                    ["LC_ALL", "LC_NUMERIC", "LANG"].iter()
+                        .filter_map(|var| env::var(var).ok())
+                        .find(|value| !value.is_empty())
+                        .unwrap_or_default()
+                } else {
+                    tag
+                };
+                match tag.as_str() {
+                    "C" | "POSIX" | "" => options.locale = None,
+                    _ => match NumberLocale::parse(&tag) {
+                        Some(locale) => options.locale = Some(locale),
+                        None => {
+                            eprintln!("Unknown locale {}, try e.g. en-US or de-DE.", tag);
+                            return ArgumentResult::ArgumentError;
+                        }
+                    },
+                }
+            }
+
+            "--no-color" => {
+                options.color = false;
+            }
+
+            "--theme" => {
+                let value = params.next().unwrap_or_default();
+                match Theme::parse(&value) {
+                    Some(theme) => options.theme = theme,
+                    None => {
+                        eprintln!("Invalid theme: {} (use default, light, mono or high-contrast)", value);
+                        return ArgumentResult::ArgumentError;
+                    }
+                }
+            }
+
+            "-f" | "--force" => {
+                options.force = true;
+            }
+
+            "--offline" => {
+                options.offline = true;
+            }
+
+            "--rates-file" => {
+                options.rates_file = Some(PathBuf::from(params.next().unwrap_or_default()));
+            }
+
+            "-o" | "--output" => {
+                options.output = Some(PathBuf::from(params.next().unwrap_or_default()));
+            }
+
+            "--compare" => {
+                options.compare = Some(PathBuf::from(params.next().unwrap_or_default()));
+            }
+
+            "--url" => {
+                let url = params.next().unwrap_or_default();
+                if !is_valid_url(&url) {
+                    eprintln!("Invalid url: {} (it has to start with http:// or https://)", url);
+                    return ArgumentResult::ArgumentError;
+                }
+                options.url = Some(url);
+            }
+
+            "--proxy" => {
+                let proxy = params.next().unwrap_or_default();
+                if proxy.is_empty() {
+                    eprintln!("--proxy needs a proxy url, e.g. http://proxy:8080");
+                    return ArgumentResult::ArgumentError;
+                }
+                options.proxy = Some(proxy);
+            }
+
+            "--api-key" => {
+                let key = params.next().unwrap_or_default();
+                if key.is_empty() {
+                    eprintln!("--api-key needs the key of the provider, try: {} --help", prg_name);
+                    return ArgumentResult::ArgumentError;
+                }
+                options.api_key = Some(key);
+            }
+
+            "--timeout" => {
+                let value = params.next().unwrap_or_default();
+                match value.parse::<u64>() {
+                    Ok(timeout) if timeout > 0 => options.timeout = timeout,
+                    _ => {
+                        eprintln!("Invalid timeout: {}", value);
+                        return ArgumentResult::ArgumentError;
+                    }
+                }
+            }
+
+            "--retries" => {
+                let value = params.next().unwrap_or_default();
+                match value.parse::<u32>() {
+                    Ok(retries) => options.retries = retries,
+                    Err(_) => {
+                        eprintln!("Invalid number of retries: {}", value);
+                        return ArgumentResult::ArgumentError;
+                    }
+                }
+            }
+
+            "--max-age" => {
+                let value = params.next().unwrap_or_default();
+                match value.parse::<u64>() {
+                    Ok(max_age) => options.max_age = max_age,
+                    Err(_) => {
+                        eprintln!("Invalid maximum age: {}", value);
+                        return ArgumentResult::ArgumentError;
+                    }
+                }
+            }
+
+            "--stale-ok-seconds" => {
+                let value = params.next().unwrap_or_default();
+                match value.parse::<u64>() {
+                    Ok(stale_ok) => options.stale_ok = stale_ok,
+                    Err(_) => {
+                        eprintln!("Invalid stale limit: {}", value);
+                        return ArgumentResult::ArgumentError;
+                    }
+                }
+            }
+
+            "--min-currencies" => {
+                let value = params.next().unwrap_or_default();
+                match value.parse::<usize>() {
+                    Ok(min_currencies) => options.min_currencies = min_currencies,
+                    Err(_) => {
+                        eprintln!("Invalid minimum number of currencies: {}", value);
+                        return ArgumentResult::ArgumentError;
+                    }
+                }
+            }
+
+            "-p" | "--precision" => {
+                let value = params.next().unwrap_or_default();
+                match value.parse::<usize>() {
+                    Ok(precision) if precision <= MAX_PRECISION => exchange.precision = Some(precision),
+                    _ => {
+                        eprintln!("Invalid precision: {} (use 0 to {})", value, MAX_PRECISION);
+                        return ArgumentResult::ArgumentError;
+                    }
+                }
+                precision_given = true;
+            }
+
+            "--rate-precision" => {
+                let value = params.next().unwrap_or_default();
+                match value.parse::<usize>() {
+                    Ok(precision) if precision <= MAX_PRECISION => exchange.rate_precision = Some(precision),
+                    _ => {
+                        eprintln!("Invalid rate precision: {} (use 0 to {})", value, MAX_PRECISION);
+                        return ArgumentResult::ArgumentError;
+                    }
+                }
+            }
+
+            "--cents" => {
+                cents = true;
+            }
+
+            "--round" => {
+                let value = params.next().unwrap_or_default();
+                round_given = true;
+                match RoundMode::parse(&value) {
+                    Some(mode) => exchange.round = mode,
+                    None => {
+                        eprintln!("Invalid rounding mode: {} (use nearest, bankers, floor or ceil)", value);
+                        return ArgumentResult::ArgumentError;
+                    }
+                }
+            }
+
+            "--fee" => {
+                let value = params.next().unwrap_or_default();
+                match value.parse::<f64>() {
+                    Ok(fee) if (0.0..100.0).contains(&fee) => exchange.fee = Some(fee),
+                    _ => {
+                        eprintln!("Invalid fee: {} (use a percentage from 0 to below 100)", value);
+                        return ArgumentResult::ArgumentError;
+                    }
+                }
+            }
+
+            "--provider" => {
+                let name = params.next().unwrap_or_default();
+                match find_provider(&name) {
+                    Some(provider) => options.providers = vec![provider],
+                    None => {
+                        eprintln!("Unknown provider: {}", name);
+                        return ArgumentResult::ArgumentError;
+                    }
+                }
+            }
+
+            "--provider-chain" => {
+                options.providers.clear();
+                for name in params.next().unwrap_or_default().split(',') {
+                    match find_provider(name.trim()) {
+                        Some(provider) => options.providers.push(provider),
+                        None => {
+                            eprintln!("Unknown provider: {}", name);
+                            return ArgumentResult::ArgumentError;
+                        }
+                    }
+                }
+            }
+
+            _ => {
+
+                let is_number = parse_amount(&param).is_ok();
+                if !positional_only && !is_number && param.starts_with('-') {
+                    eprintln!("Unkown argument: {}", param);
+                    return ArgumentResult::ArgumentError;
+                }
+
+                positionals.push(param);
+
+            }
+
+        }
+
+    }
+
+    // --cents is --precision 2 --round half-up, it beats the default
+    // precision of the config file and the decimals of the currency
+    if cents {
+        if precision_given || round_given {
+            eprintln!("--cents already rounds half-up to 2 decimals, it can't be used with --precision or --round.");
+            return ArgumentResult::ArgumentError;
+        }
+        exchange.precision = Some(2);
+        exchange.round = RoundMode::HalfUp;
+    }
+
+    if options.grouped && !matches!(list, Some(ArgumentResult::SuccessAndUsualList)) {
+        eprintln!("--grouped only groups --list-usual.");
+        return ArgumentResult::ArgumentError;
+    }
+
+    if subcommand == Some(Subcommand::Convert) && list.is_some() {
+        eprintln!("convert can't be used with the list options, try: {} list", prg_name);
+        return ArgumentResult::ArgumentError;
+    }
+
+    if let Some(list) = list {
+        if matches!(list, ArgumentResult::SuccessAndHealth) && options.rates_file.is_some() {
+            eprintln!("--health checks the providers, it can't be used with --rates-file.");
+            return ArgumentResult::ArgumentError;
+        }
+        if matches!(list, ArgumentResult::SuccessAndDiff) && (options.rates_file.is_some() || options.offline) {
+            eprintln!("--diff downloads fresh rates, it can't be used with --rates-file or --offline.");
+            return ArgumentResult::ArgumentError;
+        }
+        if matches!(list, ArgumentResult::SuccessAndAuditNames) && (options.rates_file.is_some() || options.offline) {
+            eprintln!("--audit-names checks fresh rates, it can't be used with --rates-file or --offline.");
+            return ArgumentResult::ArgumentError;
+        }
+        // the lists show rates, they never convert
+        if interactive || batch || stdin_amount || rate_only || options.all_targets || options.compare.is_some() {
+            eprintln!("The list options can't be combined with a conversion.");
+            return ArgumentResult::ArgumentError;
+        }
+        if positionals.iter().any(|param| param.chars().any(|c| c.is_ascii_digit())) {
+            eprintln!("The list options can't be combined with a conversion: {}", positionals.join(" "));
+            return ArgumentResult::ArgumentError;
+        }
+        // a word after --list filters it like --search, e.g. "--list kron"
+        if !positionals.is_empty() {
+            if options.search.is_some() || matches!(list, ArgumentResult::SuccessAndAuditNames | ArgumentResult::SuccessAndHealth | ArgumentResult::SuccessAndDiff) {
+                eprintln!("Unexpected arguments: {}", positionals.join(" "));
+                return ArgumentResult::ArgumentError;
+            }
+            options.search = Some(positionals.join(" "));
+        }
+        return list;
+    }
+
+    if options.search.is_some() {
+        eprintln!("--search only filters --list-usual and --list-all.");
+        return ArgumentResult::ArgumentError;
+    }
+
+    // --rate and --stdin-amount take no amount, every number is a code
+    if rate_only || stdin_amount {
+        if let Err(err) = replace_numeric_codes(&mut positionals, false) {
+            eprintln!("{}", err);
+            return ArgumentResult::ArgumentError;
         }
     }
-}
 
-pub fn run() -> bool {
+    // "100 USD EUR" is read as "USD EUR 100"
+    move_amount_last(&mut positionals);
 
-    let mut rates: HashMap<String, f64> = HashMap::new();
-    let mut exchange = ExchangeProcess::new();
+    // the amount is converted into the currency of --percent-of
+    if let Some((_, currency)) = &options.percent_of {
+        if rate_only || options.all_targets || options.rank || options.round_trip || options.spread.is_some() || options.reverse || options.compare.is_some() || interactive || batch {
+            eprintln!("--percent-of can't be used with --rate, --all-targets, --rank, --round-trip, --spread, --reverse, --compare, --interactive or --batch.");
+            return ArgumentResult::ArgumentError;
+        }
+        let currencies = positionals.iter().filter(|param| looks_like_currency(param)).count();
+        if currencies > 1 {
+            eprintln!("--percent-of converts into {}, give only the source and the amount.", currency);
+            return ArgumentResult::ArgumentError;
+        }
+        // parse_conversion makes the --percent-of currency the target
+        if currencies == 0 && options.default_from.is_none() {
+            eprintln!("--percent-of needs the currency of the amount, e.g. --percent-of 100 {} EUR 85", currency);
+            return ArgumentResult::ArgumentError;
+        }
+    }
 
-    if !check_rates_file() {
-        if !download_rates_file() {
-            eprintln!("Error downloading the currency data.");
-            process::exit(1);
+    // --rate converts nothing, so there is no amount to give
+    if rate_only {
+        if interactive || batch || stdin_amount || options.all_targets || options.rank || options.round_trip || options.compare.is_some() || exchange.fee.is_some() {
+            eprintln!("--rate can't be used with --interactive, --batch, --stdin-amount, --all-targets, --rank, --round-trip, --compare or --fee.");
+            return ArgumentResult::ArgumentError;
+        }
+        if let Some(amount) = positionals.iter().find(|param| !looks_like_currency(param)) {
+            eprintln!("--rate only prints the exchange rate, leave out the amount {}.", amount);
+            return ArgumentResult::ArgumentError;
         }
     }
 
-    if !load_rates_file_from_disk(&mut rates) {
-        eprintln!("Error loading currency data from disk.");
-        process::exit(2);
+    if interactive && batch {
+        eprintln!("--interactive and --batch can't be used together.");
+        return ArgumentResult::ArgumentError;
     }
 
-    let func = parse_arguments(&mut exchange);
-    match func {
-        ArgumentResult::ArgumentError => process::exit(3),
-        ArgumentResult::SuccessAndUsualList => {
-            print_usual_rates(&rates);
-            process::exit(0)
+    if interactive || batch {
+        // the conversions are read line by line instead
+        if !positionals.is_empty() || stdin_amount {
+            eprintln!("{} reads the conversions from stdin, don't pass them as arguments.",
+                      if batch { "--batch" } else { "--interactive" });
+            return ArgumentResult::ArgumentError;
         }
-        ArgumentResult::SuccessAndCompleteList => {
-            print_all_rates(&rates);
-            process::exit(0)
+    } else if options.all_targets {
+        if let Err(err) = replace_numeric_codes(&mut positionals, true) {
+            eprintln!("{}", err);
+            return ArgumentResult::ArgumentError;
+        }
+        // only the source and the amount, the targets are all currencies
+        if positionals.len() > 2 || positionals.iter().skip(1).any(|param| looks_like_currency(param)) {
+            eprintln!("--all-targets converts into every currency, give only the source and the amount.");
+            return ArgumentResult::ArgumentError;
+        }
+        if positionals.first().is_some_and(|first| !looks_like_currency(first)) {
+            if let Some(from) = &options.default_from {
+                positionals.insert(0, from.to_string());
+            }
+        }
+        if positionals.is_empty() {
+            eprintln!("Not enough arguments, try: {} --help", prg_name);
+            return ArgumentResult::MissingArguments;
+        }
+        if let Err(err) = check_currency_code(&positionals[0]) {
+            eprintln!("{}", err);
+            return ArgumentResult::ArgumentError;
+        }
+        exchange.from = positionals[0].to_ascii_uppercase();
+        exchange.amount_from = 1.0;
+        if let Some(amount) = positionals.get(1) {
+            match parse_amount(amount) {
+                Ok(amount) => exchange.amount_from = amount,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    return ArgumentResult::ArgumentError;
+                }
+            }
+        }
+    } else if positionals.is_empty() || (positionals.len() < 2 && options.default_from.is_none() && options.default_to.is_none() && options.percent_of.is_none()) {
+        eprintln!("Not enough arguments, try: {} --help", prg_name);
+        return ArgumentResult::MissingArguments;
+    } else {
+        if stdin_amount && positionals.len() > 2 && !looks_like_currency(&positionals[positionals.len() - 1]) {
+            eprintln!("Amount {} given together with --stdin-amount.", positionals[positionals.len() - 1]);
+            return ArgumentResult::ArgumentError;
+        }
+        if let Err(err) = parse_conversion(positionals, exchange, options) {
+            eprintln!("{}", err);
+            return ArgumentResult::ArgumentError;
         }
-        _ => (),
     }
 
-    if !rates.contains_key(&exchange.from) {
-        println!("Did not found currency {}.", exchange.from);
-        process::exit(4)
+    options.stdin_amount = stdin_amount;
+
+    if options.url.is_some() && options.providers.len() > 1 {
+        eprintln!("A custom url can't be used with a provider chain.");
+        return ArgumentResult::ArgumentError;
     }
-    if !rates.contains_key(&exchange.to) {
-        println!("Did not found currency {}.", exchange.to);
-        process::exit(5)
+
+    if options.rates_file.is_some() && (options.force || options.url.is_some()) {
+        eprintln!("--rates-file never downloads, it can't be used with --force or --url.");
+        return ArgumentResult::ArgumentError;
     }
 
-    exchange.rate = rates[&exchange.to] / rates[&exchange.from];
-    exchange.amount_to = exchange.amount_from * exchange.rate;
-    //dbg!(&exchange);
+    if options.quiet && options.json {
+        eprintln!("--quiet and --json can't be used together.");
+        return ArgumentResult::ArgumentError;
+    }
 
-    println!("\x1B[24mActual exchange rate:\x1B[0m \x1B[92m{}\x1B[39m \x1B[93m{:.4}\x1B[39m = \x1B[92m{}\x1B[39m \x1B[93m{:.4}\x1B[39m",
-             exchange.from,
-             exchange.amount_from,
-             exchange.to,
-             exchange.amount_to
-             );
+    if options.rank && (options.reverse || options.compare.is_some() || interactive || batch) {
+        eprintln!("--rank can't be used with --reverse, --compare, --interactive or --batch.");
+        return ArgumentResult::ArgumentError;
+    }
 
-    true
+    if options.round_trip {
+        if exchange.fee.is_none() {
+            eprintln!("--round-trip needs the fee of the exchange service, e.g. --fee 1.");
+            return ArgumentResult::ArgumentError;
+        }
+        if options.rank || options.reverse || options.compare.is_some() || interactive || batch {
+            eprintln!("--round-trip can't be used with --rank, --reverse, --compare, --interactive or --batch.");
+            return ArgumentResult::ArgumentError;
+        }
+    }
 
-}
+    if options.spread.is_some() && (rate_only || options.rank || options.round_trip || options.compare.is_some() || interactive || batch) {
+        eprintln!("--spread can't be used with --rate, --rank, --round-trip, --compare, --interactive or --batch.");
+        return ArgumentResult::ArgumentError;
+    }
 
-fn check_rates_file() -> bool {
+    if options.api_key.is_some() && options.url.is_none() {
+        eprintln!("Warning: --api-key is only sent to --url, the built-in providers get no key.");
+    }
 
-    let file_name = Path::new(get_temp_dir().as_str()).join(DEFAULT_FILENAME);
-    if !file_name.exists() {
-        println!("A local copy of {} didn't exist.", file_name.display());
-        return false;
+    if options.verify && (options.offline || options.rates_file.is_some()) {
+        eprintln!("Warning: --verify can't fetch the direct rate with --offline or --rates-file, only the round trip is checked.");
     }
 
-    let file = match File::open(&file_name) {
-        Ok(file) => file,
-        Err(err) => {
-            eprintln!("Couldn't open {} (error: {}).", file_name.display(), err);
-            return false
-        },
+    if options.offline && options.force {
+        eprintln!("--offline and --force can't be used together.");
+        return ArgumentResult::ArgumentError;
+    }
+
+    if interactive {
+        return ArgumentResult::SuccessAndInteractive;
+    }
+
+    if batch {
+        return ArgumentResult::SuccessAndBatch;
+    }
+
+    if rate_only {
+        return ArgumentResult::SuccessAndRate;
+    }
+
+    ArgumentResult::Success
+
+}
+
+/// Splits off a leading subcommand and replaces it with its flag,
+/// "list --all" becomes "--list-all" and "check" "--health". Without
+/// one the arguments are an implicit convert, as they always were.
+fn split_subcommand(params: &[String]) -> (Option<Subcommand>, Vec<String>) {
+
+    let subcommand = match params.first().and_then(|word| Subcommand::parse(word)) {
+        Some(subcommand) => subcommand,
+        None => return (None, params.to_vec()),
     };
 
-    let metadata = match file.metadata() {
-        Ok(metadata) => metadata,
-        Err(err) => {
-            eprintln!("Couldn't get metadata from file {} (error: {}).", file_name.display(), err);
-            return false
+    let mut rest = params[1..].to_vec();
+    let flag = match subcommand {
+        Subcommand::Convert => None,
+        Subcommand::List => match rest.iter().position(|param| param == "--all") {
+            Some(index) => {
+                rest.remove(index);
+                Some("--list-all")
+            }
+            None => Some("--list-usual"),
         },
+        Subcommand::Diff => Some("--diff"),
+        Subcommand::Check => Some("--health"),
     };
+    if let Some(flag) = flag {
+        rest.insert(0, flag.to_string());
+    }
 
-    let mut file_date: u64 = 0;
-    if let Ok(time) = metadata.modified() {
-        match time.duration_since(UNIX_EPOCH) {
-            Ok(t) => file_date = t.as_secs(),
-            _ => (),
+    (Some(subcommand), rest)
+}
+
+fn check_currency_code(code: &str) -> Result<(), String> {
+    if code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Ok(());
+    }
+    // nothing is loaded yet, so the suggestions come from the ISO codes
+    let mut message = format!("{} is not a currency code, they have three letters like USD or EUR.", code);
+    let suggestions = suggest_currencies(&code.to_ascii_uppercase(), NUMERIC_CODES.iter().map(|(_, alpha)| *alpha));
+    if !suggestions.is_empty() {
+        message = format!("{} Did you mean: {}?", message, suggestions.join(", "));
+    }
+    Err(message)
+}
+
+/// Moves a single amount among the currency codes to the end, where
+/// parse_conversion expects it.
+fn move_amount_last(positionals: &mut Vec<String>) {
+    let mut amounts = positionals.iter().enumerate().filter(|(_, param)| !looks_like_currency(param));
+    if let (Some((index, _)), None) = (amounts.next(), amounts.next()) {
+        let amount = positionals.remove(index);
+        positionals.push(amount);
+    }
+}
+
+/// Replaces ISO 4217 numeric codes like 840 with their alpha code.
+/// With several numbers only the last one is the amount, unless there
+/// is no amount to give; the others have to be known numeric codes.
+fn replace_numeric_codes(positionals: &mut [String], with_amount: bool) -> Result<(), String> {
+
+    let numbers: Vec<usize> = positionals.iter()
+        .enumerate()
+        .filter(|(_, param)| !looks_like_currency(param))
+        .map(|(index, _)| index)
+        .collect();
+
+    for index in numbers.iter().rev().skip(if with_amount { 1 } else { 0 }) {
+        let param = &positionals[*index];
+        match NUMERIC_CODES.iter().find(|(numeric, _)| numeric == param) {
+            Some((_, code)) => positionals[*index] = code.to_string(),
+            None => return Err(format!("{} is not a known ISO 4217 numeric code, only one amount can be given.", param)),
         }
     }
 
-    let mut cur_date: u64 = 0;
-    let now = SystemTime::now();
-    match now.duration_since(UNIX_EPOCH) {
-        Ok(t) => cur_date = t.as_secs(),
-        _ => (),
+    Ok(())
+}
+
+/// Fills in the source, targets and amount from the conversion
+/// arguments, e.g. ["USD", "EUR", "GBP", "100"].
+fn parse_conversion(mut positionals: Vec<String>, exchange: &mut ExchangeProcess, options: &Options) -> Result<(), String> {
+
+    replace_numeric_codes(&mut positionals, true)?;
+
+    // "100" converts with the default pair from the config file,
+    // "USD 100" and "USD" into the default target
+    if positionals.first().is_some_and(|first| !looks_like_currency(first)) {
+        if let Some(from) = &options.default_from {
+            positionals.insert(0, from.to_string());
+        }
+    }
+    // the currency of --percent-of is always the target
+    let default_to = options.percent_of.as_ref().map(|(_, currency)| currency).or(options.default_to.as_ref());
+    if !positionals.is_empty() && !positionals.iter().skip(1).any(|param| looks_like_currency(param)) {
+        if let Some(to) = default_to {
+            positionals.insert(1, to.to_string());
+        }
     }
 
-    if cur_date - file_date >= 3_600 {
-        return false;
+    if positionals.len() < 2 {
+        return Err(String::from("A conversion needs at least a source and a target currency."));
     }
 
-    true
+    exchange.from = positionals.remove(0).to_ascii_uppercase();
+
+    // the last positional is the amount, unless it looks like
+    // another target currency
+    exchange.amount_from = 1.0;
+    let last = &positionals[positionals.len() - 1];
+    if positionals.len() > 1 && !looks_like_currency(last) {
+        exchange.amount_from = parse_amount(last)?;
+        positionals.pop();
+    }
+
+    // anything left over has to be a target, "USD 5 EUR 100" is a mistake
+    if let Some(param) = positionals.iter().find(|param| !looks_like_currency(param)) {
+        return Err(format!("{} is not a currency code, only one amount can be given.", param));
+    }
+
+    // catch typos like "US" before anything is loaded, whether the
+    // code exists is only known from the rates
+    check_currency_code(&exchange.from)?;
+    for target in positionals.iter() {
+        check_currency_code(target)?;
+    }
+
+    exchange.targets = positionals.iter()
+        .map(|target| target.to_ascii_uppercase())
+        .collect();
+
+    Ok(())
 
 }
 
-fn download_rates_file() -> bool {
+/// Parses an amount like "1234.5", "1,234.5", "1.234,5", "12,5" or
+/// "one hundred".
+fn parse_amount(text: &str) -> Result<f64, String> {
 
-    let file_name = Path::new(get_temp_dir().as_str()).join(DEFAULT_FILENAME);
-    let file = match File::create(&file_name) {
-        Ok(file) => file,
-        Err(err) => {
-            eprintln!("Couldn't create {} (error: {}).", file_name.display(), err);
-            return false;
+    let text = text.trim();
+    let number = match text.parse::<f64>() {
+        Ok(number) => number,
+        // amounts written as words are only tried when the
+        // token isn't a plain number
+        Err(_) => match parse_number_words(text) {
+            Some(number) => number,
+            None => normalize_separators(text)?
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid amount: {}", text))?,
         },
     };
 
-    let mut writer = BufWriter::new(file);
+    // "inf" and "NaN" parse as floats, but aren't amounts
+    if !number.is_finite() {
+        return Err(format!("Invalid amount: {}", text));
+    }
 
-    let mut handle = Easy::new();
-    handle.url(INET_DL_ADDR).unwrap();
+    Ok(number)
 
-    let mut transfer = handle.transfer();
-    transfer.write_function(|data| {
-        writer.write_all(data).unwrap();
-        Ok(data.len())
-    }).unwrap();
+}
 
-    let recv = match transfer.perform() {
-        Err(err) => {
-            eprintln!("Error while download: {}", err);
-            return false
+/// Rewrites an amount with grouping and a dot or comma as decimal
+/// separator into the plain "1234.5" form.
+fn normalize_separators(text: &str) -> Result<String, String> {
+
+    let invalid = || format!("Invalid amount: {}", text);
+
+    let (integer, fraction, group) = match (text.rfind(','), text.rfind('.')) {
+        // with both, the last one separates the decimals
+        (Some(comma), Some(dot)) => {
+            let (pos, group) = if comma > dot { (comma, '.') } else { (dot, ',') };
+            (&text[..pos], &text[pos + 1..], group)
+        }
+        (Some(comma), None) => {
+            if text.matches(',').count() > 1 {
+                (text, "", ',')
+            } else if text.len() - comma - 1 == 3 {
+                return Err(format!("Ambiguous amount: {} (write it as {} or {}.00)",
+                                   text, text.replace(',', ""), text));
+            } else {
+                (&text[..comma], &text[comma + 1..], ',')
+            }
         }
-        Ok(recv) => recv,
+        (None, Some(_)) => (text, "", '.'),
+        (None, None) => return Err(invalid()),
     };
-    //dbg!(&recv);
-    true
+
+    if !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+
+    let integer = if integer.contains(group) {
+        strip_grouping(integer, group).ok_or_else(invalid)?
+    } else {
+        integer.to_string()
+    };
+
+    if fraction.is_empty() {
+        Ok(integer)
+    } else {
+        Ok(format!("{}.{}", integer, fraction))
+    }
 
 }
 
-fn load_rates_file_from_disk (exchange_rates: &mut HashMap<String, f64>) -> bool {
+/// Removes the thousands separator from e.g. "1,234,567", but only if
+/// the groups are well-formed.
+fn strip_grouping(integer: &str, separator: char) -> Option<String> {
 
-    let file_name = Path::new(get_temp_dir().as_str()).join(DEFAULT_FILENAME);
-    let file = match File::open(&file_name) {
-        Ok(file) => file,
-        Err(err) => {
-            eprintln!("Couldn't open {} (error: {}).", file_name.display(), err);
-            return false
-        },
-    };
+    let digits = integer.trim_start_matches(['-', '+']);
+
+    let mut groups = digits.split(separator);
+    let first = groups.next()?;
+    if first.is_empty() || first.len() > 3 || !first.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    for group in groups {
+        if group.len() != 3 || !group.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+    }
+
+    Some(integer.replace(separator, ""))
+
+}
 
-    let mut content = String::new();
-    let reader = BufReader::new(file);
-    for line in reader.lines() {
+/// Parses English number words like "one hundred fifty", "twenty-five
+/// thousand" or "three point five".
+fn parse_number_words(text: &str) -> Option<f64> {
 
-        let l = line.unwrap_or_default();
-        content.push_str(&l);
+    let text = text.to_lowercase();
+    let mut words = text
+        .split(|c: char| c.is_whitespace() || c == '-')
+        .filter(|word| !word.is_empty())
+        .peekable();
 
+    let mut sign = 1.0;
+    if let Some(&"minus") | Some(&"negative") = words.peek() {
+        sign = -1.0;
+        words.next();
     }
 
-    if content.len() == 0 {
-        eprintln!("File is empty.");
-        return false;
+    let mut total = 0.0;
+    let mut current = 0.0;
+    let mut found_number = false;
+
+    while let Some(word) = words.next() {
+        match word {
+            "and" => continue,
+            "hundred" => {
+                current = if current == 0.0 { 100.0 } else { current * 100.0 };
+            }
+            "thousand" | "million" | "billion" => {
+                let scale = match word {
+                    "thousand" => 1e3,
+                    "million" => 1e6,
+                    _ => 1e9,
+                };
+                total += if current == 0.0 { scale } else { current * scale };
+                current = 0.0;
+            }
+            "point" => {
+                // the digits after "point" are read one by one
+                let mut fraction = 0.0;
+                let mut divisor = 10.0;
+                for digit in words.by_ref() {
+                    let digit = number_word_value(digit).filter(|&n| n < 10.0)?;
+                    fraction += digit / divisor;
+                    divisor *= 10.0;
+                }
+                if divisor == 10.0 {
+                    return None;
+                }
+                current += fraction;
+            }
+            _ => current += number_word_value(word)?,
+        }
+        found_number = true;
     }
 
-    let json: Value = serde_json::from_str(&content).unwrap();
-    let rates = json.as_object()
-        .and_then(|object| object.get("rates"))
-        .and_then(|rates| rates.as_object())
-        .unwrap();
+    if !found_number {
+        return None;
+    }
 
-    for rate in rates.iter() {
-        let key: String = rate.0.to_string();
-        let val: f64 = rate.1.as_f64().unwrap();
-        exchange_rates.insert(key, val);
+    Some(sign * (total + current))
+
+}
+
+fn number_word_value(word: &str) -> Option<f64> {
+    let value = match word {
+        "zero" => 0,
+        "a" | "one" => 1,
+        "two" => 2,
+        "three" => 3,
+        "four" => 4,
+        "five" => 5,
+        "six" => 6,
+        "seven" => 7,
+        "eight" => 8,
+        "nine" => 9,
+        "ten" => 10,
+        "eleven" => 11,
+        "twelve" => 12,
+        "thirteen" => 13,
+        "fourteen" => 14,
+        "fifteen" => 15,
+        "sixteen" => 16,
+        "seventeen" => 17,
+        "eighteen" => 18,
+        "nineteen" => 19,
+        "twenty" => 20,
+        "thirty" => 30,
+        "forty" => 40,
+        "fifty" => 50,
+        "sixty" => 60,
+        "seventy" => 70,
+        "eighty" => 80,
+        "ninety" => 90,
+        _ => return None,
+    };
+    Some(value as f64)
+}
+
+fn is_valid_url(url: &str) -> bool {
+    let rest = url.strip_prefix("https://").or_else(|| url.strip_prefix("http://"));
+    match rest {
+        Some(rest) => !rest.is_empty() && !rest.starts_with('/') && !rest.contains(char::is_whitespace),
+        None => false,
     }
+}
 
-    true
+fn looks_like_currency(token: &str) -> bool {
+    token.chars().all(|c| c.is_ascii_alphabetic()) && parse_number_words(token).is_none()
 }
 
-pub fn get_currency_name(currency: &str) -> String {
-    let result: String;
-    match currency {
-        "EUR" => result = "Euro".to_string(),
-        "USD" => result = "US Dollar".to_string(),
-        "JPY" => result = "Japanese Yen".to_string(),
-        "BGN" => result = "Bulgarian Lev".to_string(),
-        "CZK" => result = "Czech Koruna".to_string(),
-        "DKK" => result = "Danish Krone".to_string(),
-        "GBP" => result = "Pound Sterling".to_string(),
-        "HUF" => result = "Hungarian Forint".to_string(),
-        "PLN" => result = "Polish Zloty".to_string(),
-        "RON" => result = "Romanian Leu".to_string(),
-        "SEK" => result = "Swedish Krona".to_string(),
-        "CHF" => result = "Swiss Franc".to_string(),
-        "ISK" => result = "Islandic Krona".to_string(),
-        "NOK" => result = "Norwegian Krone".to_string(),
-        "TRY" => result = "Turkish Lira".to_string(),
-        "AUD" => result = "Australian Dollar".to_string(),
-        "BRL" => result = "Brazilian Real".to_string(),
-        "CAD" => result = "Canadian Dollar".to_string(),
-        "CNY" => result = "Chinese Yuan Renmimbi".to_string(),
-        "HKD" => result = "Hong Kong Dollar".to_string(),
-        "IDR" => result = "Indonesian Rupiah".to_string(),
-        "ILS" => result = "Israeli Shekel".to_string(),
-        "INR" => result = "Indian Rupee".to_string(),
-        "KRW" => result = "South Korean Won".to_string(),
-        "MXN" => result = "Mexican Peso".to_string(),
-        "MYR" => result = "Malaysian Ringgit".to_string(),
-        "NZD" => result = "New Zealand Dollar".to_string(),
-        "PHP" => result = "Philippine Peso".to_string(),
-        "SGD" => result = "Singapore Dollar".to_string(),
-        "THB" => result = "Thai Baht".to_string(),
-        "ZAR" => result = "South African Rand".to_string(),
-        _ => result = String::from("Unknown"),
+fn read_amount_from_stdin() -> Option<f64> {
+
+    let mut input = String::new();
+    if std::io::stdin().read_to_string(&mut input).is_err() {
+        return None;
+    }
+
+    // the output of a previous run ends with the converted amount,
+    // so the last number on stdin is the one we want
+    strip_ansi_codes(&input)
+        .split_whitespace()
+        .rev()
+        .find_map(|word| word.parse::<f64>().ok())
+
+}
+
+fn strip_ansi_codes(text: &str) -> String {
+
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1B' {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            result.push(c);
+        }
     }
     result
+
 }
 
-fn get_temp_dir() -> String {
-    #[cfg(target_os="windows")]
-    let d = env::var("TEMP").unwrap_or_else(|err| {
-        eprintln!("could not find %TEMP%: {}", err);
-        String::from(".")
-    });
-    #[cfg(target_os="linux")]
-    let d = String::from("/tmp");
-    d
+/// The terminal width from $COLUMNS, 80 when it isn't known.
+fn get_terminal_width() -> usize {
+    env::var("COLUMNS").ok()
+        .and_then(|columns| columns.trim().parse::<usize>().ok())
+        .filter(|columns| *columns > 0)
+        .unwrap_or(DEFAULT_TERMINAL_WIDTH)
 }
 
-fn parse_arguments(exchange: &mut ExchangeProcess) -> ArgumentResult {
+/// Prints the rows as a table; with `grid` the table is split into as
+/// many side by side columns as fit the terminal, filled top to bottom.
+fn print_rates_table(rows: &[(&str, f64)], names: &HashMap<String, String>, precision: Option<usize>, options: &Options, grid: bool) {
 
-    let prg_name = env::args().nth(0).unwrap();
-    let version = option_env!("CARGO_PKG_VERSION").unwrap();
+    // leave the name empty rather than printing "Unknown" for every crypto or odd code
+    let rows: Vec<_> = rows.iter()
+        .map(|(key, rate)| {
+            let name = lookup_currency_name(names, key);
+            let name = if name == "Unknown" { String::new() } else { name };
+            (*key, name, format_number(*rate, rate_precision(*rate, precision), options))
+        })
+        .collect();
 
-    let mut params = env::args().skip(1);
+    let name_width = rows.iter().map(|row| row.1.chars().count()).max().unwrap_or(0).max("Currency Name".len());
+    let rate_width = rows.iter().map(|row| row.2.len()).max().unwrap_or(0).max("Rate".len());
 
-    if params.len() == 0 {
-        println!("{} needs three arguments or try --help.", prg_name);
-        std::process::exit(1);
+    let cells: Vec<_> = rows.iter()
+        .map(|(key, name, rate)| format!(" {:<3} | {:<name_width$} | {:>rate_width$}", key, name, rate))
+        .collect();
+    let cell_width = 7 + name_width + 3 + rate_width;
+
+    let columns = if grid {
+        ((get_terminal_width() + GRID_GAP.len()) / (cell_width + GRID_GAP.len())).clamp(1, cells.len().max(1))
+    } else {
+        1
+    };
+    let lines = cells.len().div_ceil(columns);
+
+    let header = format!(" Abbr| {:<name_width$} | {:>rate_width$}", "Currency Name", "Rate");
+    let rule = format!("-----|-{}-|-{}", "-".repeat(name_width), "-".repeat(rate_width));
+    out!("{}", vec![header; columns].join(GRID_GAP));
+    out!("{}", vec![rule; columns].join(GRID_GAP));
+
+    for line in 0..lines {
+        let row: Vec<_> = (0..columns)
+            .filter_map(|column| cells.get(column * lines + line))
+            .map(|cell| format!("{:<cell_width$}", cell))
+            .collect();
+        out!("{}", row.join(GRID_GAP).trim_end());
     }
 
-    let mut pos: usize = 0;
-    while let Some(param) = params.next() {
+}
+
+/// Whether the code or the name contains the --search term, ignoring case.
+fn matches_search(code: &str, names: &HashMap<String, String>, options: &Options) -> bool {
+    match &options.search {
+        Some(term) => {
+            let term = term.to_lowercase();
+            code.to_lowercase().contains(&term) || lookup_currency_name(names, code).to_lowercase().contains(&term)
+        }
+        None => true,
+    }
+}
 
-        match &param[..] {
+/// The list as a JSON array of {"code", "name", "rate"} objects, the
+/// name is null when it isn't known.
+fn print_rates_json(rows: &[(&str, f64)], names: &HashMap<String, String>, grouped: bool) {
 
-            "-h" | "--help" => {
-                print_help(&prg_name);
-                std::process::exit(0);
+    let list: Vec<Value> = rows.iter()
+        .map(|(key, rate)| {
+            let name = lookup_currency_name(names, key);
+            let mut json = serde_json::json!({
+                "code": key,
+                "name": if name == "Unknown" { Value::Null } else { Value::from(name) },
+                "rate": rate,
+            });
+            if grouped {
+                json["region"] = Value::from(region(key).name());
             }
+            json
+        })
+        .collect();
 
-            "-V" | "--version" => {
-                println!("{} v{}\n", prg_name, version);
-                std::process::exit(0);
-            }
+    out!("{}", Value::Array(list));
 
-            "-lu" | "--list-usual" | "-l" | "--list" => {
-                return ArgumentResult::SuccessAndUsualList;
-            }
+}
 
-            "-la" | "--list-all" => {
-                return ArgumentResult::SuccessAndCompleteList;
-            }
+fn print_usual_rates(rates: &HashMap<String, f64>, names: &HashMap<String, String>, as_of: &str, precision: Option<usize>, options: &Options, palette: &Palette) {
 
-            _ => {
+    // --usual or the config file replace the built-in list
+    let usual: Vec<&str> = match &options.usual {
+        Some(codes) => codes.iter().map(String::as_str).collect(),
+        None => USUAL_CURRENCIES.to_vec(),
+    };
 
-                if param.starts_with('-') {
-                    eprintln!("Unkown argument: {}", param);
-                    return ArgumentResult::ArgumentError;
-                }
+    for code in usual.iter().filter(|code| !rates.contains_key(**code)) {
+        eprintln!("Skipping {}, it isn't in the currency data.", code);
+    }
+
+    let rows: Vec<_> = usual.iter()
+        .filter_map(|key| rates.get(*key).map(|rate| (*key, *rate)))
+        .filter(|(key, _)| matches_search(key, names, options))
+        .collect();
+
+    if options.json {
+        print_rates_json(&rows, names, options.grouped);
+        return;
+    }
 
-                if pos == 0 {
+    out!("Rates as of {}{}.\n", as_of, base_suffix(options));
+    out!("{}Usual exchange rates:\n---------------------{}\n", palette.bold, palette.reset);
 
-                    exchange.from = param.to_ascii_uppercase().to_string();
-                    pos += 1;
+    if options.grouped {
+        // one table per region, regions without a currency are left out
+        for group in Region::ALL.iter() {
+            let section: Vec<_> = rows.iter()
+                .filter(|(key, _)| region(key) == *group)
+                .copied()
+                .collect();
+            if section.is_empty() {
+                continue;
+            }
+            out!("{}{}:{}", palette.bold, group.name(), palette.reset);
+            print_rates_table(&section, names, precision, options, false);
+            out!();
+        }
+    } else {
+        print_rates_table(&rows, names, precision, options, false);
+    }
 
-                } else if pos == 1 {
+    out!("\n{}Use the abbreviation to calc the exchange rates.{}", palette.bold, palette.reset)
+}
 
-                    exchange.to = param.to_ascii_uppercase().to_string();
-                    pos += 1;
+fn print_all_rates(rates: &HashMap<String, f64>, names: &HashMap<String, String>, as_of: &str, precision: Option<usize>, options: &Options, palette: &Palette) {
 
-                } else if pos == 2 {
+    let mut sorted: Vec<_> = rates.iter()
+        .map(|(key, rate)| (key.as_str(), *rate))
+        .filter(|(key, _)| matches_search(key, names, options))
+        .collect();
+    sorted.sort_by_key(|a| a.0);
 
-                    exchange.amount_from = param.parse::<f64>().unwrap_or_default();
-                    pos += 1;
+    if options.json {
+        print_rates_json(&sorted, names, false);
+        return;
+    }
 
-                } else {
-                    eprintln!("Too many arguments, try: {} --help", prg_name);
-                    return ArgumentResult::ArgumentError;
-                }
+    out!("Rates as of {}{}.\n", as_of, base_suffix(options));
+    out!("{}All available exchange rates:\n-----------------------------{}\n", palette.bold, palette.reset);
 
-            }
+    print_rates_table(&sorted, names, precision, options, true);
 
-        }
+    out!("\n{}Use the abbreviation to calc the exchange rates.{}", palette.bold, palette.reset)
 
+}
+
+fn fetch_direct_rate(from: &str, to: &str, options: &Options) -> Option<f64> {
+
+    if from == to {
+        return Some(1.0);
     }
 
-    if pos == 2 {
-        exchange.amount_from = 1.0;
-        pos += 1;
+    // --offline and --rates-file promise not to touch the network,
+    // parse_arguments already warned that the check is skipped
+    if options.offline || options.rates_file.is_some() {
+        return None;
     }
 
-    if pos != 3 {
-        eprintln!("Not enough arguments, try: {} --help", prg_name);
-        std::process::exit(1);
+    // the provider quotes the pair directly, so it can be compared
+    // against the rate we triangulated over the base currency
+    let url = format!("{}?from={}&to={}", VERIFY_DL_ADDR, from, to);
+    // a single small answer needs no progress display, and the key of
+    // --api-key is none of this provider's business
+    let request = FetchRequest { api_key: None, progress: false, ..FetchRequest::new(&url, options) };
+    let data = match fetch_rates(&request) {
+        Ok(Fetched::Body(data, _)) => data,
+        _ => {
+            eprintln!("Couldn't fetch the direct rate for {}/{}.", from, to);
+            return None
+        },
+    };
+
+    let direct = serde_json::from_slice::<Value>(&data).ok()
+        .and_then(|json| json.get("rates")?.get(to)?.as_f64());
+    match direct {
+        Some(direct) if direct > 0.0 => Some(direct),
+        _ => {
+            eprintln!("The provider has no direct rate for {}/{}.", from, to);
+            None
+        },
     }
 
-    ArgumentResult::Success
+}
+
+/// The rate back from the target to the source and its product with
+/// the forward rate, which is 1 unless the table is broken (e.g. a zero
+/// or infinite rate).
+fn round_trip(rates: &HashMap<String, f64>, exchange: &ExchangeProcess) -> (f64, f64) {
+    let inverse = exchange_rate(rates, &exchange.to, &exchange.from).unwrap_or(f64::NAN);
+    (inverse, exchange.rate * inverse)
+}
 
+fn round_trip_ok(product: f64) -> bool {
+    (product - 1.0).abs() <= ROUND_TRIP_TOLERANCE
 }
 
-fn print_usual_rates(rates: &HashMap<String, f64>) {
+fn rate_difference_bp(rate: f64, direct: f64) -> f64 {
+    (rate - direct) / direct * 10_000.0
+}
 
-    let mut sorted: Vec<_> = rates.iter().collect();
-    sorted.sort_by_key(|a| a.0);
+fn audit_currency_names(rates: &HashMap<String, f64>) -> bool {
 
-    println!("\x1B[1mUsual exchange rates:\n---------------------\x1B[0m\n");
+    let mut missing: Vec<_> = rates.keys()
+        .filter(|key| get_currency_name(key) == "Unknown")
+        .collect();
+    missing.sort();
 
-    println!(" Abbr| Currency Name\n-----|----------------------");
-    for (key, _) in sorted.iter() {
-        let rate_name = get_currency_name(&key);
-        if rate_name != "Unknown" {
-            println!(" {} | {}", key, rate_name);
-        }
+    if missing.is_empty() {
+        out!("All {} currencies have a name.", rates.len());
+        return true;
     }
 
-    println!("\n\x1B[1mUse the abbreviation to calc the exchange rates.\x1B[0m")
+    out!("{} of {} currencies have no name:", missing.len(), rates.len());
+    for key in missing.iter() {
+        out!("{}", key);
+    }
+
+    false
+
 }
 
-fn print_all_rates(rates: &HashMap<String, f64>) {
+// the subcommands, completed as the first word only
+const COMPLETION_COMMANDS: [&str; 4] = ["convert", "list", "diff", "check"];
 
-    let mut sorted: Vec<_> = rates.iter().collect();
-    sorted.sort_by_key(|a| a.0);
+const COMPLETION_FLAGS: [&str; 69] = [
+    "--", "-h", "--help", "-V", "--version", "-l", "--list", "-lu", "--list-usual",
+    "-la", "--list-all", "--audit-names", "--health", "--diff", "--search", "--usual", "--grouped", "--all-targets", "--rate", "--rank", "--round-trip", "--spread", "--spread-band", "--percent-of", "--copy", "--stdin-amount", "-i", "--interactive", "--batch",
+    "--verify", "--check", "--show-path", "-r", "--reverse", "--base", "--smart-round", "--symbol", "--locale", "-q", "--quiet", "-v", "--verbose", "--json",
+    "--no-color", "--theme", "-f", "--force", "--offline", "--rates-file", "-o", "--output", "--compare", "--url", "--proxy", "--api-key", "--timeout", "--retries",
+    "--max-age", "--stale-ok-seconds", "--min-currencies", "-p", "--precision", "--rate-precision", "--cents", "--round", "--fee", "--provider", "--provider-chain", "--completions",
+];
 
-    println!("\x1B[1mAll available exchange rates:\n-----------------------------\x1B[0m\n");
+/// Prints a completion script for bash, zsh or fish. The currency codes
+/// are taken from the cached rates at the time the script is generated.
+fn print_completions(shell: &str, prg_name: &str, options: &Options) -> bool {
 
-    for (key, _) in sorted.iter() {
-        print!("| {} ", key);
+    let command = Path::new(prg_name).file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| prg_name.to_string());
+    let function = command.replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+
+    // without a cache there are at least the usual currencies
+    let mut codes: Vec<String> = USUAL_CURRENCIES.iter().map(|code| code.to_string()).collect();
+    if let Some(provider) = options.providers.first() {
+        let mut rates = HashMap::new();
+        let mut meta = RatesMeta::default();
+        let file_name = get_rates_file_path(provider);
+        if file_name.exists() && load_rates_file_from_disk(&file_name, &mut rates, &mut meta) {
+            codes = rates.into_keys().collect();
+        }
     }
-    println!("|");
+    codes.sort();
+
+    let flags = COMPLETION_FLAGS.join(" ");
+    let providers: Vec<_> = PROVIDERS.iter().map(|provider| provider.name).collect();
+    let providers = providers.join(" ");
+    let codes = codes.join(" ");
+
+    let script = match shell {
+        "bash" => r#"_@FUNCTION@() {
+    local cur="${COMP_WORDS[COMP_CWORD]}"
+    local prev="${COMP_WORDS[COMP_CWORD-1]}"
+    case "$prev" in
+        --provider) COMPREPLY=($(compgen -W "@PROVIDERS@" -- "$cur")); return ;;
+        --rates-file|--compare|-o|--output) COMPREPLY=($(compgen -f -- "$cur")); return ;;
+        --completions) COMPREPLY=($(compgen -W "bash zsh fish" -- "$cur")); return ;;
+        --round) COMPREPLY=($(compgen -W "nearest bankers floor ceil" -- "$cur")); return ;;
+        -p|--precision|--rate-precision|--fee|--spread-band|--percent-of|--search|--usual|--theme|--locale|--max-age|--stale-ok-seconds|--min-currencies|--timeout|--retries|--url|--proxy|--api-key|--provider-chain) return ;;
+    esac
+    if [[ "$cur" == -* ]]; then
+        COMPREPLY=($(compgen -W "@FLAGS@" -- "$cur"))
+    else
+        COMPREPLY=($(compgen -W "@CODES@" -- "${cur^^}"))
+        if [[ $COMP_CWORD == 1 ]]; then
+            COMPREPLY+=($(compgen -W "@COMMANDS@" -- "$cur"))
+        fi
+    fi
+}
+complete -F _@FUNCTION@ @COMMAND@"#,
+        "zsh" => r#"#compdef @COMMAND@
+
+_@FUNCTION@() {
+    case "$words[CURRENT-1]" in
+        --provider) compadd @PROVIDERS@; return ;;
+        --rates-file|--compare|-o|--output) _files; return ;;
+        --completions) compadd bash zsh fish; return ;;
+        --round) compadd nearest bankers floor ceil; return ;;
+        -p|--precision|--rate-precision|--fee|--spread-band|--percent-of|--search|--usual|--theme|--locale|--max-age|--stale-ok-seconds|--min-currencies|--timeout|--retries|--url|--proxy|--api-key|--provider-chain) return ;;
+    esac
+    if [[ "$PREFIX" == -* ]]; then
+        compadd -- @FLAGS@
+    else
+        compadd -M 'm:{a-z}={A-Z}' @CODES@
+        (( CURRENT == 2 )) && compadd @COMMANDS@
+    fi
+}
+
+compdef _@FUNCTION@ @COMMAND@"#,
+        "fish" => r#"complete -c @COMMAND@ -f
+complete -c @COMMAND@ -n 'not string match -q -- "-*" (commandline -ct)' -a '@CODES@'
+complete -c @COMMAND@ -n '__fish_use_subcommand' -a '@COMMANDS@'
+complete -c @COMMAND@ -n 'string match -q -- "-*" (commandline -ct)' -a '@FLAGS@'
+complete -c @COMMAND@ -l provider -x -a '@PROVIDERS@'
+complete -c @COMMAND@ -l completions -x -a 'bash zsh fish'
+complete -c @COMMAND@ -l round -x -a 'nearest bankers floor ceil'
+complete -c @COMMAND@ -l rates-file -r -F
+complete -c @COMMAND@ -l compare -r -F
+complete -c @COMMAND@ -s o -l output -r -F"#,
+        _ => {
+            eprintln!("Unknown shell for --completions: {} (use bash, zsh or fish).", shell);
+            return false
+        },
+    };
+
+    println!("{}", script
+        .replace("@FUNCTION@", &function)
+        .replace("@COMMAND@", &command)
+        .replace("@PROVIDERS@", &providers)
+        .replace("@FLAGS@", &flags)
+        .replace("@COMMANDS@", &COMPLETION_COMMANDS.join(" "))
+        .replace("@CODES@", &codes));
 
-    println!("\n\x1B[1mUse the abbreviation to calc the exchange rates.\x1B[0m")
+    true
 
 }
 
 fn print_help(name: &str) {
     println!("\nUsage:");
-    println!("{} [<OPTIONS>] [CURRENCY_FROM] [CURRENCY_TO] [AMOUNT]\n", name);
+    println!("{} [<OPTIONS>] [--] [CURRENCY_FROM] [CURRENCY_TO]... [AMOUNT]", name);
+    println!("{} <COMMAND> [<OPTIONS>] [ARGUMENTS]\n", name);
+    println!("Commands:");
+    println!("convert            convert the amount, the default without a command");
+    println!("list [--all]       same as '--list-usual', or '--list-all' with --all");
+    println!("diff               same as '--diff'");
+    println!("check              same as '--health'");
+    println!();
     println!("Options:");
     println!("-l,  --list        same as '--list-usual'");
     println!("-la, --list-all    list all available currencies (long list)");
     println!("-lu, --list-usual  list the usual currencies for exchange");
+    for codes in USUAL_CURRENCIES.chunks(11) {
+        println!("                   {}", codes.join(" "));
+    }
+    println!("     --usual CODES the currencies for '--list-usual' instead, e.g. USD,EUR,GBP,BTC");
+    println!("     --grouped     group '--list-usual' by region, e.g. Europe or Americas");
+    println!("     --search TERM only list currencies whose code or name contains TERM");
+    println!("     --audit-names list currency codes without a name (exit code 6 if any)");
+    println!("     --health      check that usable currency data can be obtained, for monitoring");
+    println!("     --diff        show which rates changed between the cached copy and a fresh one");
+    println!("     --stdin-amount read the amount from stdin, e.g. the output of another run");
+    println!("     --all-targets convert the amount into every available currency");
+    println!("     --copy        also copy the converted amount to the clipboard");
+    println!("     --rate        only print the exchange rate, e.g. '1 EUR = 1.0850 USD', without an amount");
+    println!("     --rank        rank the targets by how much of them the amount buys");
+    println!("-i,  --interactive read conversions like 'USD EUR 100' line by line until 'quit'");
+    println!("     --batch       convert 'FROM TO AMOUNT' lines from stdin, one result per line");
+    println!("     --completions SHELL print a completion script for bash, zsh or fish");
+    println!("-f,  --force       download new rates even if the local copy is recent, and fail");
+    println!("                   instead of using that copy when the download fails");
+    println!("     --offline     never download, use the local copy however old it is");
+    println!("     --rates-file PATH read the rates from PATH, never download");
+    println!("-o,  --output PATH append the results to PATH instead of printing them");
+    println!("     --compare PATH show how the rates changed since the older snapshot in PATH");
+    println!("     --max-age SECONDS download new rates when the local copy is older (default 3600)");
+    println!("     --stale-ok-seconds SECONDS use an older local copy when the download fails,");
+    println!("                   up to this age (default 604800, a week; 0 never does)");
+    println!("     --min-currencies N download again when the rates have fewer currencies (default 20, 0 disables)");
+    println!("     --timeout SECONDS give up downloading after this time (default 30)");
+    println!("     --retries N   retry a failed download N times (default 3)");
+    println!("     --url URL     download the rates from this address instead");
+    println!("     --proxy URL   download through this proxy instead of HTTPS_PROXY etc.");
+    println!("     --api-key KEY send 'Authorization: Bearer KEY' to the --url endpoint, for mirrors");
+    println!("                   that need a key; prefer CURRENCY_CALC_API_KEY, other users can see arguments");
+    println!("     --provider NAME use the rates of wahrungsrechner (default) or frankfurter");
+    println!("     --provider-chain A,B try the providers in this order until one succeeds");
+    println!("-r,  --reverse     the amount is given in the target currency");
+    println!("     --base CODE   express all rates against this currency");
+    println!("     --symbol      show amounts with the currency symbol, e.g. $100.00 (USD)");
+    println!("     --locale TAG  group the digits like TAG, e.g. de-DE prints 1.234,56; auto uses LC_NUMERIC");
+    println!("     --check       convert the result back to show that no value is lost");
+    println!("     --show-path   show the step through the base currency, e.g. JPY -> EUR -> BRL");
+    println!("     --verify      check that the rate round-trips and compare it with the");
+    println!("                   provider's direct quote (in bp)");
+    println!("     --no-color    print without colors (also with NO_COLOR set or when piped)");
+    println!("     --theme NAME  the colors to use: default, light (for light backgrounds),");
+    println!("                   mono (bold and underline only) or high-contrast");
+    println!("     --json        print the result or the list as JSON, without colors");
+    println!("-v,  --verbose     log the cache, download and loading steps to stderr");
+    println!("-q,  --quiet       print only the converted amount, one line per target");
+    println!("-p,  --precision N show N decimal places (0 to 12), by default amounts get the");
+    println!("                   decimals of their currency, e.g. 2 for USD and 0 for JPY,");
+    println!("                   rates get 4 and tiny amounts as many as they need");
+    println!("     --rate-precision N show rates with N decimal places (0 to 12), a conversion");
+    println!("                   then also prints its rate; '-p 0 --rate-precision 6' gives");
+    println!("                   whole amounts and exact rates");
+    println!("     --cents       short for '--precision 2 --round half-up'");
+    println!("     --round MODE  round the converted amount with nearest (default, halves");
+    println!("                   away from zero), bankers (halves to even), floor or ceil");
+    println!("     --fee PERCENT also show the amount after an exchange fee, e.g. 2.5");
+    println!("     --round-trip  show what is left after converting there and back with --fee");
+    println!("     --spread      estimate what a bank would charge around the mid-market rate");
+    println!("     --percent-of AMOUNT CURRENCY  show the amount as a percentage of this one,");
+    println!("                   e.g. '--percent-of 100 USD EUR 85' for 85 EUR of a 100 USD budget");
+    println!("     --spread-band LOW,HIGH the markup of --spread in percent (default 1,2)");
     println!("-h,  --help        show this help");
     println!("-V,  --version     show the program version and exit");
-    println!("");
+    println!();
     println!("Exchange arguments:");
     println!("CURRENCY_FROM      The currency you have.");
-    println!("CURRENCY_TO        The currency you want to change into.");
-    println!("AMOUNT             The amount you want to change.");
-    println!("");
+    println!("CURRENCY_TO        The currency you want to change into, several are allowed.");
+    println!("AMOUNT             The amount you want to change, it may also come first,");
+    println!("                   e.g. 100 USD EUR.");
+    println!("                   Negative amounts (debits) can follow '--', e.g. -- USD EUR -50.");
+    println!("                   Both 1,234.56 and 1.234,56 are understood, as is 12,5.");
+    println!("                   Amounts can also be written in words, e.g. \"one hundred fifty\".");
+    println!("                   Currencies can also be given by their ISO 4217 number, e.g.");
+    println!("                   '840 978 100' for 'USD EUR 100'; the last number is the amount.");
+    println!();
+    println!("Environment:");
+    println!("CURRENCY_CALC_MAX_AGE  default for --max-age");
+    println!("CURRENCY_CALC_URL      default for --url");
+    println!("CURRENCY_CALC_API_KEY  default for --api-key");
+    println!("CURRENCY_CALC_CACHE_DIR  the directory for the cached rates, instead of the default");
+    println!("NO_COLOR               disables colors when set");
+    println!("HTTPS_PROXY, HTTP_PROXY, ALL_PROXY, NO_PROXY  the proxy to use, see --proxy");
+    println!();
+    if let Some(config) = get_config_path() {
+        println!("Defaults are read from {}:", config.display());
+        println!("default_from = \"EUR\", default_to = \"USD\" and default_precision = 2 make");
+        println!("'{} 100' convert 100 EUR to USD with 2 decimal places.", name);
+        println!("usual = \"USD,EUR,GBP\" sets the currencies for '--list-usual'.");
+        println!();
+    }
+    println!("Own currency names can be added in {}", Path::new(get_temp_dir().as_str()).join(NAMES_FILENAME).display());
+    println!("as a JSON object like {{\"XAU\": \"Gold\"}}.");
+    println!();
+    println!("Exit codes:");
+    for error in AppError::ALL.iter() {
+        println!("{:<2} {}", error.exit_code(), error.description());
+    }
+    println!();
+    println!("Chaining conversions:");
+    println!("{} USD EUR 100 | {} --stdin-amount EUR GBP", name, name);
+    println!();
 }
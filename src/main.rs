@@ -1,9 +1,10 @@
+use std::process;
 use currency::*;
 
-fn main() -> std::result::Result<(), u32> {
+fn main() {
 
-    run();
-
-    Ok(())
+    if let Err(err) = run() {
+        process::exit(err.exit_code());
+    }
 
 }